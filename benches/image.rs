@@ -4,9 +4,11 @@ use mandelox::coord::Viewbox;
 use mandelox::painter::Greyscale;
 use mandelox::painter::IValuePainter;
 use mandelox::painter::Painter;
+use mandelox::solver::Lazy;
 use mandelox::solver::MbState;
 use mandelox::solver::Solver;
 use mandelox::solver::VecSolver;
+use mandelox::solver::VecState;
 use mandelox::threads::Call;
 use mandelox::threads::Join;
 use mandelox::threads::Split;
@@ -21,8 +23,7 @@ where
     let scale = Viewbox::initial(width.try_into().unwrap(), height.try_into().unwrap());
 
     let solver = S::default().threaded(threads);
-    let initial = scale.generate_complex_coordinates().into();
-    let solved = solver.call(initial);
+    let solved = solver.call(Lazy::viewbox(scale));
 
     if paint {
         let painter = IValuePainter::new(Greyscale, 100);
@@ -42,18 +43,18 @@ where
 
 fn main() {
     BenchmarkReport::with_benches(&[
-        benchmark_image::<VecSolver, _>(1, 500, true),
-        benchmark_image::<VecSolver, _>(2, 500, true),
-        benchmark_image::<VecSolver, _>(4, 500, true),
-        benchmark_image::<VecSolver, _>(8, 500, true),
-        benchmark_image::<VecSolver, _>(1, 1000, true),
-        benchmark_image::<VecSolver, _>(2, 1000, true),
-        benchmark_image::<VecSolver, _>(4, 1000, true),
-        benchmark_image::<VecSolver, _>(8, 1000, true),
-        benchmark_image::<VecSolver, _>(1, 2000, true),
-        benchmark_image::<VecSolver, _>(2, 2000, true),
-        benchmark_image::<VecSolver, _>(4, 2000, true),
-        benchmark_image::<VecSolver, _>(8, 2000, true),
+        benchmark_image::<VecSolver, VecState>(1, 500, true),
+        benchmark_image::<VecSolver, VecState>(2, 500, true),
+        benchmark_image::<VecSolver, VecState>(4, 500, true),
+        benchmark_image::<VecSolver, VecState>(8, 500, true),
+        benchmark_image::<VecSolver, VecState>(1, 1000, true),
+        benchmark_image::<VecSolver, VecState>(2, 1000, true),
+        benchmark_image::<VecSolver, VecState>(4, 1000, true),
+        benchmark_image::<VecSolver, VecState>(8, 1000, true),
+        benchmark_image::<VecSolver, VecState>(1, 2000, true),
+        benchmark_image::<VecSolver, VecState>(2, 2000, true),
+        benchmark_image::<VecSolver, VecState>(4, 2000, true),
+        benchmark_image::<VecSolver, VecState>(8, 2000, true),
     ])
     .report("image");
 }
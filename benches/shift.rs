@@ -0,0 +1,39 @@
+use std::cell::RefCell;
+
+use mandelox::bench::{Benchmark, BenchmarkReport};
+use mandelox::coord::Viewbox;
+use mandelox::solver::simdvec::SimdVecState;
+use mandelox::solver::{ArrayState, D2ArrayLike, VecState};
+
+const WIDTH: i64 = 3840;
+const HEIGHT: i64 = 2160;
+
+/// Pan a 4K state by (x, y) pixels, copying the exposed bands from fresh states.
+fn b_shift<T>(name: &str, x: i64, y: i64) -> Benchmark
+where
+    T: D2ArrayLike + From<Viewbox> + 'static,
+{
+    let v = Viewbox::initial(WIDTH, HEIGHT);
+    let state: RefCell<T> = RefCell::new(v.into());
+    let rows: T = v.region(0, 0, WIDTH, y.abs()).into();
+    let cols: T = v.region(0, 0, x.abs(), HEIGHT).into();
+
+    let f = move || {
+        let mut state = state.borrow_mut();
+        state.shift_rows(y, Some(&rows));
+        state.shift_cols(x, Some(&cols));
+    };
+    Benchmark::iter(&format!("{}-{}x{}", name, x, y), 10, f)
+}
+
+fn main() {
+    BenchmarkReport::with_benches(&[
+        b_shift::<ArrayState>("ndarray", 64, 64),
+        b_shift::<VecState>("vec", 64, 64),
+        b_shift::<SimdVecState>("vecuv", 64, 64),
+        b_shift::<ArrayState>("ndarray", -3, -5),
+        b_shift::<VecState>("vec", -3, -5),
+        b_shift::<SimdVecState>("vecuv", -3, -5),
+    ])
+    .report("shift");
+}
@@ -2,8 +2,10 @@ use std::collections::HashSet;
 
 use mandelox::bench::{Benchmark, BenchmarkReport};
 use mandelox::coord::Viewbox;
-use mandelox::solver::{ArraySolver, MbState, SimdVecSolver, Solver, VecSolver};
-use mandelox::threads::Call;
+use mandelox::solver::{
+    ArraySolver, Lazy, MbState, SharedVecState, SimdVecSolver, Solver, VecSolver, VecState,
+};
+use mandelox::threads::Join;
 
 fn thread_counts() -> Vec<usize> {
     let cpus = num_cpus::get_physical();
@@ -21,16 +23,30 @@ fn thread_counts() -> Vec<usize> {
     tcounts
 }
 
-fn benchmark_solver<S, T, U>(name: &str, solver: S, height: usize, repeats: usize) -> Benchmark
+fn benchmark_solver<S, T>(name: &str, solver: S, height: usize, repeats: usize) -> Benchmark
 where
     T: MbState + 'static + Clone,
-    S: Call<T, U> + 'static,
+    S: Solver<T> + 'static,
 {
     let width: usize = (3 * height) / 2;
     let v = Viewbox::initial(width.try_into().unwrap(), height.try_into().unwrap());
-    let initial: T = v.generate_complex_coordinates().into();
+    let initial: T = v.into();
     let f = move || {
-        solver.call(initial.clone());
+        solver.solve(initial.clone());
+    };
+    Benchmark::iter(&format!("{}  {:>4}", name, height), repeats, f)
+}
+
+/// Like `benchmark_solver`, but from a viewbox, so building the state is timed too.
+fn benchmark_solver_lazy<S, T>(name: &str, solver: S, height: usize, repeats: usize) -> Benchmark
+where
+    T: MbState + Join + 'static,
+    S: Solver<T> + 'static,
+{
+    let width: usize = (3 * height) / 2;
+    let v = Viewbox::initial(width.try_into().unwrap(), height.try_into().unwrap());
+    let f = move || {
+        solver.solve_lazy(Lazy::viewbox(v));
     };
     Benchmark::iter(&format!("{}  {:>4}", name, height), repeats, f)
 }
@@ -59,18 +75,50 @@ fn benchmarks(height: usize, repeats: usize) -> Vec<Benchmark> {
             height,
             repeats,
         ));
-        benches.push(benchmark_solver(
+        benches.push(benchmark_solver::<_, VecState>(
             &format!("vec      {:>2}t", t),
             VecSolver::default().threaded(t),
             height,
             repeats,
         ));
+        benches.push(benchmark_solver::<_, SharedVecState>(
+            &format!("sharedvec{:>2}t", t),
+            VecSolver::default().threaded(t),
+            height,
+            repeats,
+        ));
+        benches.push(benchmark_solver_lazy::<_, VecState>(
+            &format!("vec lazy {:>2}t", t),
+            VecSolver::default().threaded(t),
+            height,
+            repeats,
+        ));
+        benches.push(benchmark_solver_lazy::<_, SharedVecState>(
+            &format!("sharedlzy{:>2}t", t),
+            VecSolver::default().threaded(t),
+            height,
+            repeats,
+        ));
         benches.push(benchmark_solver(
             &format!("simdvec  {:>2}t", t),
             SimdVecSolver::default().threaded(t),
             height,
             repeats,
         ));
+        #[cfg(feature = "rayon")]
+        benches.push(benchmark_solver::<_, VecState>(
+            &format!("vecrayon {:>2}t", t),
+            mandelox::solver::RayonSolver::new(VecSolver::default()).with_pool(
+                std::sync::Arc::new(
+                    rayon::ThreadPoolBuilder::new()
+                        .num_threads(t)
+                        .build()
+                        .unwrap(),
+                ),
+            ),
+            height,
+            repeats,
+        ));
     }
     benches
 }
@@ -1,5 +1,5 @@
 use mandelox::bench::{Benchmark, BenchmarkReport};
-use mandelox::threads::{vectorize, Call, Threaded};
+use mandelox::threads::{vectorize, Call, Threaded, DEFAULT_CHUNKS_PER_WORKER};
 
 const TASK_MEM_SIZE: usize = 10000;
 const TASK_CPU_SIZE: usize = 10000;
@@ -12,6 +12,20 @@ fn square(mut x: i64) -> i64 {
     x
 }
 
+/// Like `square`, but the last tenth of the input costs 100 times more, like
+/// rows going through the set.
+fn skewed(mut x: i64) -> i64 {
+    let n = if x as usize >= TASK_MEM_SIZE * 9 / 10 {
+        TASK_CPU_SIZE * 10
+    } else {
+        TASK_CPU_SIZE / 10
+    };
+    for _ in 0..n {
+        x = (x * x) + x;
+    }
+    x
+}
+
 fn vrange(n: usize) -> Vec<i64> {
     (0..n).map(|n| n as i64).collect()
 }
@@ -34,6 +48,19 @@ fn bench_threadpool(threads: usize) -> Benchmark {
     }
 }
 
+/// Skewed workload, split into `chunks` parts per worker; one chunk per worker
+/// is the static scheduling the pool used to do.
+fn bench_skewed(threads: usize, chunks: usize) -> Benchmark {
+    let name = &format!("skewed-t{}-c{}", threads, chunks);
+    let f = vectorize(skewed)
+        .threadpool(threads)
+        .with_chunks_per_worker(chunks);
+    let c = move || {
+        f.call(vrange(TASK_MEM_SIZE));
+    };
+    Benchmark::iter(name, ITN, c)
+}
+
 fn main() {
     BenchmarkReport::with_benches(&[
         bench_threadpool(0),
@@ -41,6 +68,10 @@ fn main() {
         bench_threadpool(2),
         bench_threadpool(4),
         bench_threadpool(8),
+        bench_skewed(4, 1),
+        bench_skewed(4, DEFAULT_CHUNKS_PER_WORKER),
+        bench_skewed(8, 1),
+        bench_skewed(8, DEFAULT_CHUNKS_PER_WORKER),
     ])
     .report("workerpool");
 }
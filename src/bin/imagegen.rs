@@ -1,7 +1,24 @@
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
 use structopt::StructOpt;
 
-use mandelox::mandelbrot;
-use mandelox::painter::Rainbow;
+use mandelox::coord::Viewbox;
+use mandelox::defaults;
+use mandelox::painter::{IValuePainter, Painter, Rainbow};
+use mandelox::session::Session;
+use mandelox::solver::{Lazy, Solver};
+use mandelox::threads::{Progress, ProgressReport};
+use mandelox::RenderSettings;
+
+/// Chunks per worker; more than the pool default, so the progress bar moves
+/// smoothly on large images.
+const CHUNKS_PER_WORKER: usize = 64;
+const BAR_WIDTH: usize = 40;
 
 #[derive(Debug, StructOpt)]
 struct Opt {
@@ -11,12 +28,73 @@ struct Opt {
     height: i64,
     #[structopt(short, long, default_value = "out.png")]
     output: String,
+    /// Render the frames of a recorded session instead, numbered after the
+    /// output file, e.g. out-00000.png.
+    #[structopt(long)]
+    session: Option<String>,
+}
+
+fn progress_bar(report: &ProgressReport) -> String {
+    let filled = (report.fraction() * BAR_WIDTH as f64) as usize;
+    format!(
+        "[{}{}] {}",
+        "#".repeat(filled),
+        " ".repeat(BAR_WIDTH - filled),
+        report
+    )
+}
+
+/// Draw a progress bar on stderr until `done` is set.
+fn spawn_progress_bar(progress: Progress, done: Arc<AtomicBool>) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut stderr = std::io::stderr();
+        loop {
+            let finished = done.load(Ordering::SeqCst);
+            let _ = write!(stderr, "\r{}", progress_bar(&progress.report()));
+            let _ = stderr.flush();
+            if finished {
+                let _ = writeln!(stderr);
+                return;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    })
+}
+
+/// Save each frame of the session at `path`, numbered after `output`.
+fn render_session(path: &str, output: &str) {
+    let session = Session::load(path).expect("failed to load session");
+    let output = Path::new(output);
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = output.extension().unwrap_or_default().to_string_lossy();
+    for frame in session.frames(RenderSettings::default()) {
+        let name = format!("{}-{:05}.{}", stem, frame.sequence, extension);
+        eprint!("\r{}", name);
+        frame
+            .image
+            .save(output.with_file_name(name))
+            .expect("failed to save image");
+    }
+    eprintln!();
 }
 
 fn main() {
     let opt = Opt::from_args();
-    mandelbrot(opt.width, opt.height)
-        .paint(Rainbow, 100)
+    if let Some(ref session) = opt.session {
+        return render_session(session, &opt.output);
+    }
+    let solver =
+        Solver::<defaults::State>::threaded(defaults::Solver::default(), num_cpus::get_physical())
+            .with_chunks_per_worker(CHUNKS_PER_WORKER);
+    let done = Arc::new(AtomicBool::new(false));
+    let bar = spawn_progress_bar(solver.progress(), done.clone());
+
+    let state = solver.solve_lazy(Lazy::viewbox(Viewbox::initial(opt.width, opt.height)));
+    done.store(true, Ordering::SeqCst);
+    bar.join().unwrap();
+
+    IValuePainter::new(Rainbow, 100)
+        .paint(&state)
         .save(opt.output)
         .expect("failed to save image");
 }
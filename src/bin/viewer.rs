@@ -1,13 +1,43 @@
+use std::sync::Arc;
+
 use druid::{AppLauncher, PlatformError, WindowDesc};
 
 use mandelox::gui::widget::MandelbrotWidget;
+use mandelox::remote::RemoteControl;
+use mandelox::session::SessionRecorder;
+use mandelox::MandelbrotWorker;
+
+const USAGE: &str = "usage: mandelox-viewer [--record <file>] [--listen <host:port | socket>]";
 
 fn main() -> Result<(), PlatformError> {
+    let worker = Arc::new(MandelbrotWorker::new());
+    let mut remote = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().expect(USAGE);
+        match arg.as_str() {
+            // Record the session, to replay it.
+            "--record" => worker
+                .record(SessionRecorder::create(value).expect("failed to create session file")),
+            // Take commands from scripts or other viewers.
+            "--listen" if value.contains(':') => {
+                remote =
+                    Some(RemoteControl::bind_tcp(worker.clone(), value).expect("failed to listen"))
+            }
+            #[cfg(unix)]
+            "--listen" => {
+                remote =
+                    Some(RemoteControl::bind_unix(worker.clone(), value).expect("failed to listen"))
+            }
+            _ => panic!("{}", USAGE),
+        }
+    }
     AppLauncher::with_window(
-        WindowDesc::new(MandelbrotWidget::new())
+        WindowDesc::new(MandelbrotWidget::with_worker(worker))
             .title("Mandelox")
             .window_size((1000.0, 800.0)),
     )
     .launch(())?;
+    drop(remote);
     Ok(())
 }
@@ -10,6 +10,7 @@ pub fn cr(re: f64) -> C<f64> {
     c(re, 0.0)
 }
 
+#[cfg(test)]
 pub fn ci(im: f64) -> C<f64> {
     c(0.0, im)
 }
@@ -1,6 +1,13 @@
-use crate::{complex::*, solver::D2ArrayLike};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
-#[derive(Copy, Clone, Debug)]
+use serde::{Deserialize, Serialize};
+
+use crate::complex::*;
+use crate::solver::{copy_cells_from, copy_cells_self, D2ArrayLike, RowMajor};
+use crate::threads::Join;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Point<T>
 where
     T: num::Num + Copy,
@@ -30,16 +37,35 @@ where
     }
 }
 
+impl Point<usize> {
+    pub fn as_i64(self) -> Point<i64> {
+        Point::new(self.x as i64, self.y as i64)
+    }
+}
+
 impl<T> Point<T>
 where
     T: num::Num + Copy,
     f64: From<T>,
 {
     pub fn as_f64(self) -> Point<f64> {
-        Point::<f64>::new(self.x.try_into().unwrap(), self.y.try_into().unwrap())
+        Point::<f64>::new(self.x.into(), self.y.into())
     }
 }
 
+/// If `factor` is an exact power of two, its base-2 exponent.
+pub fn power_of_two(factor: f64) -> Option<i32> {
+    const MANTISSA: u64 = (1 << 52) - 1;
+    if !factor.is_normal() || factor < 0.0 || factor.to_bits() & MANTISSA != 0 {
+        return None;
+    }
+    Some(((factor.to_bits() >> 52) as i32) - 1023)
+}
+
+/// Largest offset a power-of-two zoom keeps, so that the pixel arithmetic on
+/// the grid, e.g. of tiles, can't overflow.
+const MAX_OFFSET: i64 = 1 << 48;
+
 #[derive(Clone, Debug)]
 pub struct Coords<T> {
     pub(crate) width: usize,
@@ -76,61 +102,393 @@ where
         let v = self.values[from.row_idx(self.width)].clone();
         self.values[to.row_idx(self.width)] = v;
     }
+
+    fn copy_span_from(&mut self, other: &Self, from: Point<usize>, to: Point<usize>, len: usize) {
+        copy_cells_from(self, other, from, to, len);
+    }
+
+    fn copy_span_self(&mut self, from: Point<usize>, to: Point<usize>, len: usize) {
+        copy_cells_self(self, from, to, len);
+    }
+
+    fn row_contiguous(&self) -> bool {
+        true
+    }
+}
+
+impl<T> RowMajor for Coords<T>
+where
+    T: Default + Clone,
+{
+    type Cell = T;
+
+    fn cells(&self) -> &[T] {
+        &self.values
+    }
+
+    fn cells_mut(&mut self) -> &mut [T] {
+        &mut self.values
+    }
+}
+
+impl<T> Join for Coords<T> {
+    fn join_vec(parts: Vec<Self>) -> Self {
+        let width = parts[0].width;
+        let mut height = 0;
+        let mut values = vec![];
+        for part in parts {
+            assert!(part.width == width, "different width");
+            height += part.height;
+            values.extend(part.values);
+        }
+        Self {
+            width,
+            height,
+            values,
+        }
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
+/// Affine map from pixel coordinates to the complex plane:
+/// `re = a * x + b * y + e` and `im = c * x + d * y + f`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Affine {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Affine {
+    pub fn new(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Self {
+        Self { a, b, c, d, e, f }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+    }
+
+    pub fn translation(x: f64, y: f64) -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, x, y)
+    }
+
+    pub fn scaling(x: f64, y: f64) -> Self {
+        Self::new(x, 0.0, 0.0, y, 0.0, 0.0)
+    }
+
+    pub fn rotation(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new(cos, -sin, sin, cos, 0.0, 0.0)
+    }
+
+    /// The transform that applies `self`, then `other`.
+    pub fn then(&self, other: &Affine) -> Affine {
+        Affine::new(
+            other.a * self.a + other.b * self.c,
+            other.a * self.b + other.b * self.d,
+            other.c * self.a + other.d * self.c,
+            other.c * self.b + other.d * self.d,
+            other.a * self.e + other.b * self.f + other.e,
+            other.c * self.e + other.d * self.f + other.f,
+        )
+    }
+
+    pub fn determinant(&self) -> f64 {
+        self.a * self.d - self.b * self.c
+    }
+
+    pub fn inverse(&self) -> Option<Affine> {
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let a = self.d / det;
+        let b = -self.b / det;
+        let c = -self.c / det;
+        let d = self.a / det;
+        Some(Affine::new(
+            a,
+            b,
+            c,
+            d,
+            -(a * self.e + b * self.f),
+            -(c * self.e + d * self.f),
+        ))
+    }
+
+    pub fn apply(&self, p: Point<f64>) -> C<f64> {
+        c(
+            self.a * p.x + self.b * p.y + self.e,
+            self.c * p.x + self.d * p.y + self.f,
+        )
+    }
+
+    pub fn apply_i64(&self, p: &Point<i64>) -> C<f64> {
+        self.apply(Point::new(p.x as f64, p.y as f64))
+    }
+}
+
+/// A rectangular view of the complex plane, sampled on a grid of pixels.
+///
+/// The authoritative position is a complex `center` and a pixel `spacing`. Pans
+/// are recorded as an exact integer pixel `offset` from that center, so that
+/// panning never moves the pixel grid and already solved pixels can be reused.
+/// Pixels are addressed by their global coordinates on that grid, relative to
+/// `center`; the frame's top-left pixel is `origin()`.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Viewbox {
-    pub(crate) center: Point<i64>,
+    pub(crate) center: C<f64>,
+    /// Distance between horizontally adjacent pixels in the complex plane.
+    pub(crate) spacing: f64,
+    /// Pixel offset of the view center from `center`, accumulated by pans.
+    pub(crate) offset: Point<i64>,
     pub(crate) width: i64,
     pub(crate) height: i64,
-    pub(crate) scale: f64,
+    /// Counter-clockwise rotation of the pixel grid, in radians.
+    pub(crate) rotation: f64,
+    /// Ratio of the vertical to the horizontal pixel spacing.
+    pub(crate) aspect: f64,
 }
 
 impl Viewbox {
-    pub fn new(x: i64, y: i64, w: i64, h: i64, scale: f64) -> Self {
+    pub fn new(re: f64, im: f64, w: i64, h: i64, spacing: f64) -> Self {
         Self {
-            center: Point::new(x, y),
+            center: c(re, im),
+            spacing,
+            offset: Point::new(0, 0),
             width: w,
             height: h,
-            scale,
+            rotation: 0.0,
+            aspect: 1.0,
         }
     }
 
     pub fn initial(width: i64, height: i64) -> Self {
         let aspect_ratio = width as f64 / height as f64;
-        let scale = if aspect_ratio > 1.25 {
-            height as f64 / 2.4
+        let spacing = if aspect_ratio > 1.25 {
+            2.4 / height as f64
         } else {
-            width as f64 / 3.0
+            3.0 / width as f64
         };
-        let mut this = Self::new(0, 0, width, height, scale);
-        this.center.x = this.scale(-0.5);
-        this
+        Self::new(-0.5, 0.0, width, height, spacing)
+    }
+
+    pub fn with_rotation(mut self, angle: f64) -> Self {
+        self.rotate(angle);
+        self
+    }
+
+    pub fn with_aspect(mut self, aspect: f64) -> Self {
+        self.set_aspect(aspect);
+        self
+    }
+
+    /// Complex coordinate at the center of the view, including pans.
+    pub fn view_center(&self) -> C<f64> {
+        self.unscaled(&self.offset)
+    }
+
+    pub fn spacing(&self) -> f64 {
+        self.spacing
+    }
+
+    pub fn rotation(&self) -> f64 {
+        self.rotation
+    }
+
+    pub fn aspect(&self) -> f64 {
+        self.aspect
+    }
+
+    /// The same view at `1 / factor` of the resolution, e.g. for a preview.
+    pub fn downscaled(&self, factor: i64) -> Viewbox {
+        assert!(factor > 0, "scale factor must be positive");
+        let center = self.view_center();
+        let width = (self.width / factor).max(1);
+        let height = (self.height / factor).max(1);
+        Viewbox::new(
+            center.re,
+            center.im,
+            width,
+            height,
+            self.spacing * factor as f64,
+        )
+        .with_rotation(self.rotation)
+        .with_aspect(self.aspect)
+    }
+
+    /// The same view at `factor` times the resolution, e.g. for an export.
+    pub fn upscaled(&self, factor: i64) -> Viewbox {
+        assert!(factor > 0, "scale factor must be positive");
+        let center = self.view_center();
+        Viewbox::new(
+            center.re,
+            center.im,
+            self.width * factor,
+            self.height * factor,
+            self.spacing / factor as f64,
+        )
+        .with_rotation(self.rotation)
+        .with_aspect(self.aspect)
+    }
+
+    /// Whether the viewbox maps its pixels to finite points, e.g. one read
+    /// from a session or a client, with its offset in range.
+    pub(crate) fn is_finite(&self) -> bool {
+        [self.center.re, self.center.im, self.rotation]
+            .iter()
+            .all(|v| v.is_finite())
+            && [self.spacing, self.aspect]
+                .iter()
+                .all(|&v| v.is_finite() && v > 0.0)
+            && self.offset.x.abs() <= MAX_OFFSET
+            && self.offset.y.abs() <= MAX_OFFSET
+    }
+
+    /// Move the view center to the current view position and reset the offset.
+    ///
+    /// This moves the pixel grid, so it is only done when the grid changes anyway.
+    fn fold_offset(&mut self) {
+        self.center = self.view_center();
+        self.offset = Point::new(0, 0);
+    }
+
+    pub fn pan(&mut self, x: i64, y: i64) {
+        self.offset = self.offset.add(&Point::new(x, y));
     }
 
+    /// Zoom about the view center.
+    ///
+    /// A power-of-two zoom keeps the grid anchored at `center` whenever the
+    /// scaled offset is still an integer, so every other pixel of the new grid
+    /// coincides exactly with a pixel of the old one.
     pub fn zoom(&mut self, factor: f64) {
-        let C { re, im } = self.unscaled(&self.center);
-        self.scale *= factor;
-        self.center = Point::new(self.scale(re), self.scale(im))
+        if let Some(k) = power_of_two(factor) {
+            // Scaling overflows on deep zooms; the offset is folded then.
+            let step = 1i64.checked_shl(k.unsigned_abs()).filter(|&step| step > 0);
+            let scaled = step.and_then(|step| {
+                if k >= 0 {
+                    let x = self.offset.x.checked_mul(step)?;
+                    let y = self.offset.y.checked_mul(step)?;
+                    Some(Point::new(x, y))
+                } else if self.offset.x % step == 0 && self.offset.y % step == 0 {
+                    Some(Point::new(self.offset.x / step, self.offset.y / step))
+                } else {
+                    None
+                }
+            });
+            if let Some(offset) =
+                scaled.filter(|o| o.x.abs() <= MAX_OFFSET && o.y.abs() <= MAX_OFFSET)
+            {
+                self.offset = offset;
+                self.spacing /= factor;
+                return;
+            }
+        }
+        self.fold_offset();
+        self.spacing /= factor;
+    }
+
+    /// Zoom about the given pixel of the frame, keeping the complex coordinate
+    /// under it fixed, like a scroll-wheel zoom under the mouse cursor.
+    pub fn zoom_at(&mut self, factor: f64, pixel: Point<f64>) {
+        let origin = self.origin();
+        let anchor = self.transform().apply(Point::new(
+            origin.x as f64 + pixel.x,
+            origin.y as f64 + pixel.y,
+        ));
+        let center = self.view_center();
+        self.center = anchor + (center - anchor) / factor;
+        self.offset = Point::new(0, 0);
+        self.spacing /= factor;
+    }
+
+    pub fn rotate(&mut self, angle: f64) {
+        self.fold_offset();
+        self.rotation = (self.rotation + angle) % std::f64::consts::TAU;
+    }
+
+    pub fn set_aspect(&mut self, aspect: f64) {
+        assert!(aspect > 0.0, "aspect must be positive");
+        self.fold_offset();
+        self.aspect = aspect;
+    }
+
+    /// Top-left pixel of the viewbox.
+    pub fn origin(&self) -> Point<i64> {
+        Point::new(
+            self.offset.x - (self.width / 2),
+            self.offset.y - (self.height / 2),
+        )
+    }
+
+    /// Identifies the pixel grid of the viewbox (center, spacing, rotation and
+    /// aspect): two viewboxes with the same zoom level map any given pixel to
+    /// exactly the same complex coordinate.
+    pub fn zoom_level(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.center.re.to_bits().hash(&mut hasher);
+        self.center.im.to_bits().hash(&mut hasher);
+        self.spacing.to_bits().hash(&mut hasher);
+        self.rotation.to_bits().hash(&mut hasher);
+        self.aspect.to_bits().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Transform from global pixel coordinates to the complex plane; every
+    /// complex coordinate of the viewbox is computed through it.
+    ///
+    /// The spacing is applied last, so that halving it maps pixel 2p to exactly
+    /// the same coordinate as pixel p before.
+    pub fn transform(&self) -> Affine {
+        let (sin, cos) = self.rotation.sin_cos();
+        Affine::new(
+            cos * self.spacing,
+            (-sin * self.aspect) * self.spacing,
+            sin * self.spacing,
+            (cos * self.aspect) * self.spacing,
+            self.center.re,
+            self.center.im,
+        )
     }
 
-    pub fn scale(&self, coord: f64) -> i64 {
-        f64::round(self.scale * coord) as i64
+    /// Nearest pixel to a complex coordinate.
+    pub fn pixel(&self, c: C<f64>) -> Point<i64> {
+        let inverse = self.transform().inverse().expect("degenerate viewbox");
+        let p = inverse.apply(Point::new(c.re, c.im));
+        Point::new(f64::round(p.re) as i64, f64::round(p.im) as i64)
     }
 
     pub fn unscaled(&self, p: &Point<i64>) -> C<f64> {
-        let cx = cr((p.x as f64) / self.scale);
-        let cy = ci((p.y as f64) / self.scale);
-        cx + cy
+        self.transform().apply_i64(p)
     }
 
-    pub fn generate_complex_coordinates(&self) -> Coords<C<f64>> {
-        let mut grid = vec![];
-        for (x, y) in self.into_iter() {
-            grid.push(self.unscaled(&Point::new(x, y)));
+    /// The sub-rectangle of this viewbox's frame whose top-left pixel is (x, y),
+    /// on the same pixel grid.
+    pub fn region(&self, x: i64, y: i64, width: i64, height: i64) -> Viewbox {
+        assert!(x >= 0 && x + width <= self.width, "region out of bounds");
+        assert!(y >= 0 && y + height <= self.height, "region out of bounds");
+        let origin = self.origin().add(&Point::new(x, y));
+        Viewbox {
+            offset: origin.add(&Point::new(width / 2, height / 2)),
+            width,
+            height,
+            ..*self
         }
+    }
+
+    /// Complex coordinates of the pixels, in row-major order, computed on the fly.
+    pub fn coordinates(self) -> impl Iterator<Item = C<f64>> {
+        let transform = self.transform();
+        self.into_iter()
+            .map(move |(x, y)| transform.apply_i64(&Point::new(x, y)))
+    }
+
+    pub fn generate_complex_coordinates(&self) -> Coords<C<f64>> {
         Coords {
-            values: grid,
+            values: self.coordinates().collect(),
             width: self.width as usize,
             height: self.height as usize,
         }
@@ -176,10 +534,17 @@ impl IntoIterator for Viewbox {
     type Item = (i64, i64);
     type IntoIter = ViewboxIter;
     fn into_iter(self) -> <Self as IntoIterator>::IntoIter {
-        let from_x = self.center.x - (self.width / 2);
+        let Point {
+            x: from_x,
+            y: from_y,
+        } = self.origin();
         let to_x = from_x + self.width - 1;
-        let from_y = self.center.y - (self.height / 2);
-        let to_y = from_y + self.height - 1;
+        // An empty row would otherwise still yield one pixel per line.
+        let to_y = if self.width > 0 {
+            from_y + self.height - 1
+        } else {
+            from_y - 1
+        };
         ViewboxIter {
             x: from_x,
             from_x,
@@ -212,11 +577,142 @@ mod test {
         print_arr(&arr1);
     }
 
+    #[test]
+    fn test_power_of_two() {
+        assert_eq!(power_of_two(1.0), Some(0));
+        assert_eq!(power_of_two(2.0), Some(1));
+        assert_eq!(power_of_two(0.25), Some(-2));
+        assert_eq!(power_of_two(1.1), None);
+        assert_eq!(power_of_two(3.0), None);
+        assert_eq!(power_of_two(-2.0), None);
+        assert_eq!(power_of_two(0.0), None);
+    }
+
+    fn assert_close(a: C<f64>, b: C<f64>) {
+        assert!((a - b).norm() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_affine_inverse() {
+        let t = Affine::rotation(0.3)
+            .then(&Affine::scaling(2.0, 0.5))
+            .then(&Affine::translation(1.0, -3.0));
+        let p = Point::new(3.0, -7.0);
+        let q = t.apply(p);
+        let r = t.inverse().unwrap().apply(Point::new(q.re, q.im));
+        assert_close(c(r.re, r.im), c(p.x, p.y));
+    }
+
+    #[test]
+    fn test_viewbox_rotation() {
+        let mut viewbox = Viewbox::initial(41, 31);
+        viewbox.pan(3, -2);
+        let center = viewbox.view_center();
+        let rotated = viewbox.with_rotation(std::f64::consts::FRAC_PI_2);
+        assert_close(rotated.view_center(), center);
+        let right = rotated.offset.add(&Point::new(1, 0));
+        let step = rotated.unscaled(&right) - rotated.view_center();
+        assert_close(step, ci(viewbox.spacing));
+
+        let coords = rotated.generate_complex_coordinates();
+        for (n, (x, y)) in rotated.into_iter().enumerate() {
+            assert_eq!(coords.values[n], rotated.unscaled(&Point::new(x, y)));
+        }
+    }
+
+    #[test]
+    fn test_viewbox_aspect() {
+        let viewbox = Viewbox::initial(40, 30).with_aspect(2.0);
+        let p = viewbox.offset;
+        let dx = viewbox.unscaled(&p.add(&Point::new(1, 0))) - viewbox.unscaled(&p);
+        let dy = viewbox.unscaled(&p.add(&Point::new(0, 1))) - viewbox.unscaled(&p);
+        assert_close(dy, ci(2.0 * dx.re));
+    }
+
+    #[test]
+    fn test_viewbox_region() {
+        let mut viewbox = Viewbox::initial(9, 7).with_rotation(0.2);
+        viewbox.pan(-3, 4);
+        let coords = viewbox.generate_complex_coordinates();
+        let region = viewbox.region(2, 3, 5, 3);
+        let sub = coords.copy_slice(Point::new(2, 3), Point::new(7, 6));
+        assert_eq!(region.generate_complex_coordinates().values, sub.values);
+        assert_eq!(viewbox.region(0, 7, 9, 0).coordinates().count(), 0);
+        assert_eq!(viewbox.region(3, 0, 0, 7).coordinates().count(), 0);
+    }
+
+    #[test]
+    fn test_zoom_round_trip() {
+        let mut viewbox = Viewbox::initial(40, 30);
+        viewbox.pan(7, -5);
+        let center = viewbox.view_center();
+        for factor in [1.1, 2.0, 0.5, 0.7, 1.0 / 3.0] {
+            for _ in 0..20 {
+                viewbox.zoom(factor);
+            }
+            for _ in 0..20 {
+                viewbox.zoom(1.0 / factor);
+            }
+            assert_eq!(viewbox.view_center(), center);
+        }
+    }
+
+    #[test]
+    fn test_zoom_at_keeps_anchor() {
+        let mut viewbox = Viewbox::initial(40, 30).with_rotation(0.4);
+        viewbox.pan(3, 1);
+        let origin = viewbox.origin();
+        let pixel = Point::new(31.0, 4.0);
+        let under = viewbox.unscaled(&origin.add(&Point::new(31, 4)));
+        viewbox.zoom_at(1.7, pixel);
+        let origin = viewbox.origin();
+        assert_close(viewbox.unscaled(&origin.add(&Point::new(31, 4))), under);
+        viewbox.zoom_at(1.0 / 1.7, pixel);
+        let origin = viewbox.origin();
+        assert_close(viewbox.unscaled(&origin.add(&Point::new(31, 4))), under);
+    }
+
+    #[test]
+    fn test_zoom_power_of_two_keeps_grid() {
+        let mut viewbox = Viewbox::initial(40, 30);
+        viewbox.pan(6, -4);
+        let p = viewbox.unscaled(&Point::new(3, 5));
+        viewbox.zoom(2.0);
+        assert_eq!(viewbox.unscaled(&Point::new(6, 10)), p);
+        viewbox.zoom(0.25);
+        assert_eq!(viewbox.offset.x, 3);
+        assert_eq!(viewbox.offset.y, -2);
+        viewbox.zoom(0.5);
+        assert_eq!(viewbox.offset.x, 0);
+        assert_eq!(viewbox.offset.y, 0);
+    }
+
+    #[test]
+    fn test_zoom_overflow_folds_offset() {
+        let mut viewbox = Viewbox::initial(40, 30);
+        viewbox.pan(6, -4);
+        let center = viewbox.view_center();
+        viewbox.zoom(1e21);
+        assert_eq!(viewbox.offset, Point::new(0, 0));
+        assert_eq!(viewbox.view_center(), center);
+        viewbox.pan(1, 1);
+        viewbox.zoom(2f64.powi(-80));
+        assert_eq!(viewbox.offset, Point::new(0, 0));
+
+        // Zooming in step by step after a pan keeps the offset bounded.
+        let mut viewbox = Viewbox::initial(40, 30);
+        viewbox.pan(3, 0);
+        for _ in 0..100 {
+            viewbox.zoom(2.0);
+        }
+        assert!(viewbox.offset.x.abs() <= MAX_OFFSET);
+    }
+
     #[test]
     fn test_viewbox_initial() {
         let mut viewbox = Viewbox::initial(6, 8);
-        viewbox.center.y = 100;
-        viewbox.center.x = 200;
+        viewbox.offset.y = 100;
+        viewbox.offset.x = 200;
         let xy = viewbox.into_iter();
         assert_eq!(xy.count(), 48);
         /*
@@ -1,22 +1,35 @@
 use druid::widget::prelude::*;
-use druid::{Code, MouseButton, Size, Widget};
+use std::sync::Arc;
+use std::time::Duration;
+
+use druid::{Code, ImageBuf, MouseButton, Size, TimerToken, Widget};
 use druid::text::TextLayout;
 
 use crate::gui::convert_image;
 use crate::MandelbrotWorker;
 
 pub struct MandelbrotWidget {
-    worker: MandelbrotWorker,
+    worker: Arc<MandelbrotWorker>,
     width: i64,
     height: i64,
+    frame: Option<ImageBuf>,
+    timer: TimerToken,
 }
 
 impl MandelbrotWidget {
     pub fn new() -> Self {
+        Self::with_worker(Arc::new(MandelbrotWorker::new()))
+    }
+
+    /// Drive `worker`, e.g. one recording the session or also controlled
+    /// remotely.
+    pub fn with_worker(worker: Arc<MandelbrotWorker>) -> Self {
         Self {
-            worker: MandelbrotWorker::new(),
+            worker,
             width: 0,
             height: 0,
+            frame: None,
+            timer: TimerToken::INVALID,
         }
     }
 }
@@ -45,15 +58,29 @@ pub fn draw_text(ctx: &mut PaintCtx, env: &Env, x: f64, y: f64, text: String) {
 }
 
 const ZOOM_FACTOR: f64 = 1.1;
+// Power of two, so the worker can reuse pixels from the previous frame
+const ZOOM_STEP_FACTOR: f64 = 2.0;
 const ZOOM_WHEEL_FACTOR: f64 = 2000.0;
 const PAN_FACTOR: f64 = 0.025;
+const ROTATE_STEP: f64 = std::f64::consts::PI / 36.0;
+// How often the progress overlay is redrawn during a render
+const PROGRESS_REFRESH: Duration = Duration::from_millis(100);
 
 impl Widget<()> for MandelbrotWidget {
     fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut (), _env: &Env) {
-        if self.worker.images_count() > 0 {
+        if self.worker.frames_count() > 0 {
             ctx.request_paint();
         }
         match event {
+            Event::WindowConnected => {
+                self.timer = ctx.request_timer(PROGRESS_REFRESH);
+            }
+            Event::Timer(token) if *token == self.timer => {
+                if self.worker.progress().is_some_and(|p| !p.is_finished()) {
+                    ctx.request_paint();
+                }
+                self.timer = ctx.request_timer(PROGRESS_REFRESH);
+            }
             Event::KeyDown(key_event) => {
                 use Code::*;
                 match key_event.code {
@@ -63,6 +90,10 @@ impl Widget<()> for MandelbrotWidget {
                     ArrowRight => self.worker.pan_relative(PAN_FACTOR, 0.0),
                     PageUp => self.worker.zoom(ZOOM_FACTOR),
                     PageDown => self.worker.zoom(1.0 / ZOOM_FACTOR),
+                    Equal => self.worker.zoom(ZOOM_STEP_FACTOR),
+                    Minus => self.worker.zoom(1.0 / ZOOM_STEP_FACTOR),
+                    BracketLeft => self.worker.rotate(ROTATE_STEP),
+                    BracketRight => self.worker.rotate(-ROTATE_STEP),
                     KeyR => self.worker.reset(self.width, self.height),
                     _ => (),
                 }
@@ -88,7 +119,8 @@ impl Widget<()> for MandelbrotWidget {
                 } else {
                     1.0 + delta_y / -ZOOM_WHEEL_FACTOR
                 };
-                self.worker.zoom(zf);
+                let druid::Point { x, y } = mouse.pos;
+                self.worker.zoom_at(zf, x, y);
             }
             _ => (),
         }
@@ -116,17 +148,16 @@ impl Widget<()> for MandelbrotWidget {
         _data: &(),
         _env: &Env,
     ) -> Size {
-        let Size { width, height } = bc.max();
-        let h = ((height as u64) / 8) * 8;
-        let w = ((width as u64) / 8) * 8;
-        Size::new(w as f64, h as f64)
+        bc.max()
     }
 
-    fn paint(&mut self, ctx: &mut PaintCtx, _: &(), _env: &Env) {
+    fn paint(&mut self, ctx: &mut PaintCtx, _: &(), env: &Env) {
         let size = ctx.size();
         if !self.resize(size) {
             if let Some(rgb_image) = self.worker.next_image() {
-                let image_buf = convert_image(rgb_image);
+                self.frame = Some(convert_image(rgb_image));
+            }
+            if let Some(ref image_buf) = self.frame {
                 let ctx_image = image_buf.to_image(ctx.render_ctx);
                 ctx.draw_image(
                     &ctx_image,
@@ -135,5 +166,8 @@ impl Widget<()> for MandelbrotWidget {
                 );
             }
         }
+        if let Some(progress) = self.worker.progress().filter(|p| !p.is_finished()) {
+            draw_text(ctx, env, 0.02, 0.02, format!("Rendering {}", progress));
+        }
     }
 }
@@ -1,16 +1,25 @@
 #![allow(clippy::new_without_default)]
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use image::imageops::{self, FilterType};
 use image::RgbImage;
+use serde::{Deserialize, Serialize};
 
-use crate::coord::{Point, Viewbox};
-use crate::painter::{ColorScale, IValuePainter, Painter, Rainbow};
-use crate::solver::{D2ArrayLike, MbState, Solver};
-use crate::threads::{Join, Split};
+use crate::coord::{power_of_two, Coords, Point, Viewbox};
+use crate::painter::{ColorScale, IValuePainter, Painter, Palette};
+use crate::session::SessionRecorder;
+use crate::solver::{
+    ArraySolver, ArrayState, D2ArrayLike, Formula, Lazy, MbState, SharedVecState, SimdVecSolver,
+    SimdVecState, Solver, SolverKind, VecSolver, VecState,
+};
+use crate::threads::{CancelToken, Cancelled, Join, Progress, ProgressReport, Split};
+use crate::tile::{TileCache, TileRender};
 
 pub mod bench;
 mod complex;
@@ -18,13 +27,20 @@ pub mod coord;
 #[cfg(feature = "gui")]
 pub mod gui;
 pub mod painter;
+pub mod remote;
+pub mod render;
+pub mod session;
 pub mod solver;
 pub mod threads;
+pub mod tile;
 
 pub struct Mandelbrot<T> {
     pub solver: Box<dyn Solver<T>>,
     pub position: Viewbox,
     pub state: T,
+    tiles: Option<Box<dyn TileRender<T> + Send>>,
+    cancel: CancelToken,
+    stale: bool,
 }
 
 impl<T> Mandelbrot<T>
@@ -35,44 +51,117 @@ where
     where
         S: Solver<T> + Default + Clone + Send + 'static,
     {
-        let position = Viewbox::initial(width, height);
-        let solver = S::default().threaded(num_cpus::get_physical());
-        let initial: T = position.generate_complex_coordinates().into();
-        let solved = solver.solve(initial);
+        Self::with_solver(
+            S::default().threaded(num_cpus::get_physical()),
+            width,
+            height,
+        )
+    }
+
+    /// Like `initialize`, but solve with `solver` rather than a pool of
+    /// threads of our own, e.g. a `RayonSolver` on the application's pool.
+    pub fn with_solver<S>(solver: S, width: i64, height: i64) -> Self
+    where
+        S: Solver<T> + 'static,
+    {
+        Self::solved_at(solver, Viewbox::initial(width, height), CancelToken::new())
+    }
+
+    /// Solve `position` with `cancel`; if it is cancelled, the state is left
+    /// stale.
+    fn solved_at<S>(solver: S, position: Viewbox, cancel: CancelToken) -> Self
+    where
+        S: Solver<T> + 'static,
+    {
+        let (state, stale) = match solver.solve_lazy_cancellable(Lazy::viewbox(position), &cancel) {
+            Ok(solved) => (solved, false),
+            Err(Cancelled) => (position.into(), true),
+        };
         Self {
             position,
-            state: solved,
+            state,
             solver: Box::new(solver),
+            tiles: None,
+            cancel,
+            stale,
+        }
+    }
+
+    /// Solve with `cancel` from now on. Once it is cancelled, the render in
+    /// progress stops and the state is left stale.
+    pub fn set_cancel_token(&mut self, cancel: CancelToken) {
+        self.cancel = cancel;
+    }
+
+    /// Whether the state is out of date with the position, because its last
+    /// render was cancelled.
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Progress of the render in progress, or of the last one.
+    pub fn progress(&self) -> Option<Progress> {
+        self.solver.progress()
+    }
+
+    /// Solve the current position again, e.g. after a cancelled render.
+    pub fn refresh(&mut self) {
+        self.solve_position();
+    }
+
+    fn solve_position(&mut self) {
+        let solved = match self.tiles {
+            Some(ref mut tiles) => tiles.render(&self.position, self.solver.as_ref(), &self.cancel),
+            None => self
+                .solver
+                .solve_lazy_cancellable(Lazy::viewbox(self.position), &self.cancel),
+        };
+        self.update_state(solved);
+    }
+
+    fn solve_lazy(&self, state: Lazy<T>) -> Result<T, Cancelled> {
+        self.solver.solve_lazy_cancellable(state, &self.cancel)
+    }
+
+    fn update_state(&mut self, solved: Result<T, Cancelled>) {
+        match solved {
+            Ok(state) => {
+                self.state = state;
+                self.stale = false;
+            }
+            Err(Cancelled) => self.stale = true,
         }
     }
 
     pub fn resize(&mut self, width: i64, height: i64) {
         self.position.height = height;
         self.position.width = width;
-        self.state = self
-            .solver
-            .solve(self.position.generate_complex_coordinates().into());
+        self.solve_position();
     }
 
     pub fn set_position(&mut self, position: Viewbox) {
         self.position = position;
-        self.state = self
-            .solver
-            .solve(self.position.generate_complex_coordinates().into());
+        self.solve_position();
     }
 
     pub fn zoom(&mut self, factor: f64) {
         self.position.zoom(factor);
-        self.state = self
-            .solver
-            .solve(self.position.generate_complex_coordinates().into());
+        self.solve_position();
     }
 
     pub fn pan(&mut self, x: i64, y: i64) {
-        self.position.center = self.position.center.add(&Point::new(x, y));
-        self.state = self
-            .solver
-            .solve(self.position.generate_complex_coordinates().into());
+        self.position.pan(x, y);
+        self.solve_position();
+    }
+
+    pub fn zoom_at(&mut self, factor: f64, pixel: Point<f64>) {
+        self.position.zoom_at(factor, pixel);
+        self.solve_position();
+    }
+
+    pub fn rotate(&mut self, angle: f64) {
+        self.position.rotate(angle);
+        self.solve_position();
     }
 
     pub fn pan_relative(&mut self, x: f64, y: f64) {
@@ -94,11 +183,35 @@ impl<T> Mandelbrot<T>
 where
     T: D2ArrayLike + MbState + Split + Join + Send + 'static,
 {
+    /// Keep up to `capacity` solved tiles in memory; from then on `resize`,
+    /// `set_position`, `zoom` and `pan` assemble frames from cached tiles and
+    /// only solve the missing ones.
+    pub fn enable_tile_cache(&mut self, capacity: usize)
+    where
+        T: Clone,
+    {
+        self.tiles = Some(Box::new(TileCache::<T>::new(capacity)));
+    }
+
+    pub fn disable_tile_cache(&mut self) {
+        self.tiles = None;
+    }
+
     pub fn pan_fast_vertical(&mut self, y: i64) {
-        self.position.center = self.position.center.add(&Point::new(0, y));
-        let new_coord_rows = self.position.generate_complex_coordinates().copy_rows(-y);
-        let new_state_rows = self.solver.solve(new_coord_rows.into());
-        self.state.shift_rows(-y, Some(&new_state_rows));
+        let (w, h) = (self.position.width, self.position.height);
+        if y.abs() >= h || self.stale {
+            return self.pan(0, y);
+        }
+        self.position.pan(0, y);
+        let new_rows = if y < 0 {
+            self.position.region(0, 0, w, -y)
+        } else {
+            self.position.region(0, h - y, w, y)
+        };
+        match self.solve_lazy(Lazy::viewbox(new_rows)) {
+            Ok(new_state_rows) => self.state.shift_rows(-y, Some(&new_state_rows)),
+            Err(Cancelled) => self.stale = true,
+        }
     }
     pub fn pan_fast_vertical_relative(&mut self, y: f64) {
         let ny = (y * self.position.height as f64).round() as i64;
@@ -108,10 +221,20 @@ where
     }
 
     pub fn pan_fast_horizontal(&mut self, x: i64) {
-        self.position.center = self.position.center.add(&Point::new(x, 0));
-        let new_coord_cols = self.position.generate_complex_coordinates().copy_cols(-x);
-        let new_stat_cols = self.solver.solve(new_coord_cols.into());
-        self.state.shift_cols(-x, Some(&new_stat_cols));
+        let (w, h) = (self.position.width, self.position.height);
+        if x.abs() >= w || self.stale {
+            return self.pan(x, 0);
+        }
+        self.position.pan(x, 0);
+        let new_cols = if x < 0 {
+            self.position.region(0, 0, -x, h)
+        } else {
+            self.position.region(w - x, 0, x, h)
+        };
+        match self.solve_lazy(Lazy::viewbox(new_cols)) {
+            Ok(new_state_cols) => self.state.shift_cols(-x, Some(&new_state_cols)),
+            Err(Cancelled) => self.stale = true,
+        }
     }
     pub fn pan_fast_horizontal_relative(&mut self, x: f64) {
         let nx = (x * self.position.width as f64).round() as i64;
@@ -119,6 +242,163 @@ where
             self.pan_fast_horizontal(nx)
         }
     }
+
+    /// Zoom, reusing the samples of the current state that coincide with the new
+    /// pixel grid.
+    ///
+    /// Only power-of-two factors have coinciding samples: zooming in by 2 reuses
+    /// every other sample of every other row, zooming out by 2 reuses the whole
+    /// current state as the center quarter of the new one. `Viewbox::zoom` keeps
+    /// the grid anchored for those factors and scaling by a power of two is exact,
+    /// so the reused samples are bitwise identical to what a full solve would
+    /// compute. Other factors, and zoom-outs that have to re-anchor the grid, fall
+    /// back to a full solve.
+    pub fn zoom_fast(&mut self, factor: f64) {
+        let k = match power_of_two(factor) {
+            Some(k) if k != 0 && !self.stale => k,
+            _ => return self.zoom(factor),
+        };
+        // Pixels map onto each other every `step` pixels, if that fits at all.
+        let step = match 1i64.checked_shl(k.unsigned_abs()) {
+            Some(step) if step > 0 => step,
+            _ => return self.zoom(factor),
+        };
+        let old_center = self.position.center;
+        let old_origin = self.position.origin();
+        let old_w = self.position.width;
+        let old_h = self.position.height;
+        self.position.zoom(factor);
+        if self.position.center != old_center {
+            return self.solve_position();
+        }
+        let origin = self.position.origin();
+        let width = self.position.width as usize;
+        let height = self.position.height as usize;
+
+        let mut state = <T as D2ArrayLike>::new(width, height);
+        let mut missing: Vec<Point<usize>> = vec![];
+        for y in 0..height {
+            for x in 0..width {
+                let q = origin.add(&Point::new(x as i64, y as i64));
+                let p = if k > 0 {
+                    if q.x.rem_euclid(step) == 0 && q.y.rem_euclid(step) == 0 {
+                        Some(Point::new(q.x.div_euclid(step), q.y.div_euclid(step)))
+                    } else {
+                        None
+                    }
+                } else {
+                    q.x.checked_mul(step)
+                        .zip(q.y.checked_mul(step))
+                        .map(|(x, y)| Point::new(x, y))
+                };
+                match p.map(|p| p.add(&old_origin.mul(-1))) {
+                    Some(p) if p.x >= 0 && p.x < old_w && p.y >= 0 && p.y < old_h => {
+                        let from = Point::new(p.x as usize, p.y as usize);
+                        state.copy_from(&self.state, from, Point::new(x, y));
+                    }
+                    _ => missing.push(Point::new(x, y)),
+                }
+            }
+        }
+
+        let solved = self.solve_missing(&mut state, missing).map(|_| state);
+        self.update_state(solved);
+    }
+
+    /// Shift the state by (x, y) pixels in one go and solve only the newly
+    /// exposed L-shaped region, with a single solver call. Falls back to a full
+    /// solve when the shift is larger than the viewport.
+    pub fn pan_fast(&mut self, x: i64, y: i64) {
+        let w = self.position.width;
+        let h = self.position.height;
+        if x.abs() >= w || y.abs() >= h || self.stale {
+            return self.pan(x, y);
+        }
+        if x == 0 && y == 0 {
+            return;
+        }
+        self.position.pan(x, y);
+        let mut state = std::mem::replace(&mut self.state, <T as D2ArrayLike>::new(0, 0));
+        state.shift_rows(-y, None);
+        state.shift_cols(-x, None);
+
+        let mut missing: Vec<Point<usize>> = vec![];
+        for py in 0..h {
+            let row_exposed = !(0..h).contains(&(py + y));
+            for px in 0..w {
+                if row_exposed || !(0..w).contains(&(px + x)) {
+                    missing.push(Point::new(px as usize, py as usize));
+                }
+            }
+        }
+        // If cancelled, the state is half shifted and stays stale.
+        self.stale = self.solve_missing(&mut state, missing).is_err();
+        self.state = state;
+    }
+
+    pub fn pan_fast_relative(&mut self, x: f64, y: f64) {
+        let nx = (x * self.position.width as f64).round() as i64;
+        let ny = (y * self.position.height as f64).round() as i64;
+        self.pan_fast(nx, ny);
+    }
+
+    /// Move to `position`, through `pan_fast` or `zoom_fast` when it is a pan or
+    /// a power-of-two zoom of the current position.
+    pub fn set_position_fast(&mut self, position: Viewbox) {
+        let current = self.position;
+        if position == current && !self.stale {
+            return;
+        }
+        if (position.width, position.height) == (current.width, current.height) {
+            let (x, y) = (
+                position.offset.x - current.offset.x,
+                position.offset.y - current.offset.y,
+            );
+            let mut panned = current;
+            panned.pan(x, y);
+            if panned == position {
+                return self.pan_fast(x, y);
+            }
+            let factor = current.spacing / position.spacing;
+            let mut zoomed = current;
+            zoomed.zoom(factor);
+            if power_of_two(factor).is_some() && zoomed == position {
+                return self.zoom_fast(factor);
+            }
+        }
+        self.set_position(position);
+    }
+
+    /// Solve the given pixels of the current position and write them into `state`.
+    fn solve_missing(&self, state: &mut T, missing: Vec<Point<usize>>) -> Result<(), Cancelled> {
+        let width = self.position.width as usize;
+        let origin = self.position.origin();
+        let last = match missing.last() {
+            Some(last) => *last,
+            None => return Ok(()),
+        };
+        // Pack the missing samples into full rows so they split evenly between
+        // workers; the padding repeats the last sample and is discarded.
+        let rows = missing.len().div_ceil(width);
+        let mut values: Vec<_> = missing
+            .iter()
+            .map(|p| self.position.unscaled(&origin.add(&p.as_i64())))
+            .collect();
+        values.resize(
+            rows * width,
+            self.position.unscaled(&origin.add(&last.as_i64())),
+        );
+        let coords = Coords {
+            width,
+            height: rows,
+            values,
+        };
+        let solved = self.solver.solve_cancellable(coords.into(), &self.cancel)?;
+        for (n, p) in missing.into_iter().enumerate() {
+            state.copy_from(&solved, Point::new(n % width, n / width), p);
+        }
+        Ok(())
+    }
 }
 
 // #[cfg(target_arch = "aarch64")]
@@ -139,15 +419,508 @@ pub fn mandelbrot(width: i64, height: i64) -> Mandelbrot<defaults::State> {
     Mandelbrot::<defaults::State>::initialize::<defaults::Solver>(width, height)
 }
 
-#[derive(Copy, Clone, Debug)]
+const TILE_CACHE_CAPACITY: usize = 512;
+
+/// Largest width and height of a worker's frames.
+pub const MAX_FRAME_SIZE: i64 = 1 << 14;
+
+/// Most iterations a worker renders with, as iteration counts are `i16`.
+pub const MAX_ITERATIONS: u16 = i16::MAX as u16;
+
+/// Largest multiple of the resolution a worker exports at; an export is solved
+/// in memory as a whole.
+pub const MAX_EXPORT_SCALE: i64 = 8;
+
+/// Most pixels of an export, e.g. 8192 by 8192, whatever its scale.
+pub const MAX_EXPORT_PIXELS: i64 = 1 << 26;
+
+/// What a `MandelbrotWorker` renders with; all of it can change at runtime.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RenderSettings {
+    pub solver: SolverKind,
+    pub palette: Palette,
+    pub iterations: u16,
+    pub formula: Formula,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            solver: SolverKind::default(),
+            palette: Palette::default(),
+            iterations: 100,
+            formula: Formula::default(),
+        }
+    }
+}
+
+impl RenderSettings {
+    /// Iteration count painted with the last color of a scale.
+    pub fn max_i_value(&self) -> i16 {
+        i16::try_from(self.iterations).unwrap_or(i16::MAX)
+    }
+
+    /// Apply a settings action, or return false for other actions.
+    fn apply(&mut self, action: MAction) -> bool {
+        match action {
+            MAction::SetPalette(palette) => self.palette = palette,
+            MAction::SetIterations(iterations) => self.iterations = iterations,
+            MAction::SetSolver(solver) => self.solver = solver,
+            MAction::SetFormula(formula) => self.formula = formula,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Whether a frame is a quick preview, to be followed by the final render, or
+/// a requested export.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Quality {
+    Preview,
+    Final,
+    Export,
+}
+
+/// A frame painted by a `MandelbrotWorker`, with what it shows and how.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub image: RgbImage,
+    pub position: Viewbox,
+    /// Frames are numbered in the order they are painted, from 0.
+    pub sequence: u64,
+    /// Time from the action that led to the frame to its painting.
+    pub duration: Duration,
+    pub settings: RenderSettings,
+    pub quality: Quality,
+}
+
+/// Resolution divisor of previews.
+const PREVIEW_SCALE: i64 = 4;
+
+/// Most pixels times iterations a preview is solved for; beyond this it
+/// wouldn't be quick, so the final render is waited for instead.
+const MAX_PREVIEW_COST: i64 = 1 << 28;
+
+/// Quick render of `position` at a lower resolution, scaled back up, to show
+/// while the final render runs; `None` if that would be too slow or `cancel`
+/// is cancelled first.
+fn preview(
+    views: &dyn ViewFactory,
+    painter: &dyn FramePainter,
+    settings: &RenderSettings,
+    position: Viewbox,
+    cancel: &CancelToken,
+) -> Option<RgbImage> {
+    let small = position.downscaled(PREVIEW_SCALE);
+    if small.width * small.height * i64::from(settings.iterations) > MAX_PREVIEW_COST {
+        return None;
+    }
+    let view = views.view(settings, small, cancel.clone());
+    if view.is_stale() {
+        return None;
+    }
+    let image = painter.paint(view.as_ref(), settings);
+    Some(imageops::resize(
+        &image,
+        position.width as u32,
+        position.height as u32,
+        FilterType::Nearest,
+    ))
+}
+
+/// Numbers frames and queues them for the consumer, from the render thread of
+/// a worker.
+struct FrameSender {
+    frames: Arc<Mutex<VecDeque<Frame>>>,
+    /// Exports, queued apart so that taking the latest frame keeps them.
+    exports: Arc<Mutex<VecDeque<Frame>>>,
+    /// Frames dropped from a full queue before they were taken.
+    dropped: Arc<AtomicU64>,
+    sequence: u64,
+}
+
+impl FrameSender {
+    /// Queue a frame, or an export in the exports queue, dropping the oldest
+    /// one if the queue is full.
+    fn send(
+        &mut self,
+        image: RgbImage,
+        position: Viewbox,
+        quality: Quality,
+        settings: RenderSettings,
+        started: Instant,
+    ) {
+        let frame = Frame {
+            image,
+            position,
+            sequence: self.sequence,
+            duration: started.elapsed(),
+            settings,
+            quality,
+        };
+        self.sequence += 1;
+        let (queue, capacity) = match quality {
+            Quality::Export => (&self.exports, EXPORT_QUEUE_CAPACITY),
+            Quality::Preview | Quality::Final => (&self.frames, FRAME_QUEUE_CAPACITY),
+        };
+        let mut frames = queue.lock().unwrap();
+        if frames.len() == capacity {
+            frames.pop_front();
+            self.dropped.fetch_add(1, Ordering::SeqCst);
+        }
+        frames.push_back(frame);
+    }
+
+    /// Send a preview of `position`, if it is quick to render, before
+    /// rendering it from scratch.
+    fn send_preview(
+        &mut self,
+        views: &dyn ViewFactory,
+        painter: &dyn FramePainter,
+        settings: &RenderSettings,
+        position: Viewbox,
+        cancel: &CancelToken,
+        started: Instant,
+    ) {
+        if let Some(image) = preview(views, painter, settings, position, cancel) {
+            self.send(image, position, Quality::Preview, *settings, started)
+        }
+    }
+}
+
+/// Frames kept for the consumer of a `MandelbrotWorker`; older ones are
+/// dropped beyond this, see `MandelbrotWorker::dropped_frames`.
+pub const FRAME_QUEUE_CAPACITY: usize = 16;
+
+/// Exports kept for the consumer of a `MandelbrotWorker`, as for frames.
+pub const EXPORT_QUEUE_CAPACITY: usize = 4;
+
+/// A `Mandelbrot` of any state, so that the render thread of a worker can
+/// switch solvers.
+pub trait View {
+    fn position(&self) -> Viewbox;
+    fn set_cancel_token(&mut self, cancel: CancelToken);
+    fn is_stale(&self) -> bool;
+    fn progress(&self) -> Option<Progress>;
+    fn refresh(&mut self);
+    fn resize(&mut self, width: i64, height: i64);
+    fn set_position_fast(&mut self, position: Viewbox);
+    fn pan_fast(&mut self, x: i64, y: i64);
+    fn pan_fast_relative(&mut self, x: f64, y: f64);
+    fn zoom_fast(&mut self, factor: f64);
+    fn zoom_at(&mut self, factor: f64, pixel: Point<f64>);
+    fn rotate(&mut self, angle: f64);
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn i_value(&self, x: usize, y: usize) -> i16;
+
+    fn paint(&self, palette: Palette, max_i_value: i16) -> RgbImage {
+        IValuePainter::new(palette, max_i_value).paint_pixels(
+            self.width(),
+            self.height(),
+            |x, y| self.i_value(x, y),
+        )
+    }
+}
+
+impl<T> View for Mandelbrot<T>
+where
+    T: D2ArrayLike + MbState + Split + Join + Send + 'static,
+{
+    fn position(&self) -> Viewbox {
+        self.position
+    }
+    fn set_cancel_token(&mut self, cancel: CancelToken) {
+        Mandelbrot::set_cancel_token(self, cancel)
+    }
+    fn is_stale(&self) -> bool {
+        Mandelbrot::is_stale(self)
+    }
+    fn progress(&self) -> Option<Progress> {
+        Mandelbrot::progress(self)
+    }
+    fn refresh(&mut self) {
+        Mandelbrot::refresh(self)
+    }
+    fn resize(&mut self, width: i64, height: i64) {
+        Mandelbrot::resize(self, width, height)
+    }
+    fn set_position_fast(&mut self, position: Viewbox) {
+        Mandelbrot::set_position_fast(self, position)
+    }
+    fn pan_fast(&mut self, x: i64, y: i64) {
+        Mandelbrot::pan_fast(self, x, y)
+    }
+    fn pan_fast_relative(&mut self, x: f64, y: f64) {
+        Mandelbrot::pan_fast_relative(self, x, y)
+    }
+    fn zoom_fast(&mut self, factor: f64) {
+        Mandelbrot::zoom_fast(self, factor)
+    }
+    fn zoom_at(&mut self, factor: f64, pixel: Point<f64>) {
+        Mandelbrot::zoom_at(self, factor, pixel)
+    }
+    fn rotate(&mut self, angle: f64) {
+        Mandelbrot::rotate(self, angle)
+    }
+    fn width(&self) -> usize {
+        MbState::width(&self.state)
+    }
+    fn height(&self) -> usize {
+        MbState::height(&self.state)
+    }
+    fn i_value(&self, x: usize, y: usize) -> i16 {
+        self.state.i_value(x, y)
+    }
+}
+
+/// A view of `position` solved with `solver`, e.g. a `RayonSolver`, with a
+/// tile cache; it is stale if `cancel` is cancelled before it is solved.
+pub fn solved_view<S, T>(solver: S, position: Viewbox, cancel: CancelToken) -> Box<dyn View>
+where
+    S: Solver<T> + 'static,
+    T: D2ArrayLike + MbState + Split + Join + Clone + Send + 'static,
+{
+    let mut m = Mandelbrot::solved_at(solver, position, cancel);
+    m.enable_tile_cache(TILE_CACHE_CAPACITY);
+    Box::new(m)
+}
+
+/// Like `solved_view`, on a pool of threads of our own.
+pub fn cached_view<S, T>(solver: S, position: Viewbox, cancel: CancelToken) -> Box<dyn View>
+where
+    S: Solver<T> + Clone + Send + 'static,
+    T: D2ArrayLike + MbState + Split + Join + Clone + Send + 'static,
+{
+    solved_view(
+        Solver::<T>::threaded(solver, num_cpus::get_physical()),
+        position,
+        cancel,
+    )
+}
+
+/// Builds the views a `MandelbrotWorker` renders, each time its position is
+/// reset or its iterations, solver or formula change.
+pub trait ViewFactory: Send {
+    /// A view of `position` rendered as set in `settings`, solved with
+    /// `cancel`.
+    fn view(
+        &self,
+        settings: &RenderSettings,
+        position: Viewbox,
+        cancel: CancelToken,
+    ) -> Box<dyn View>;
+}
+
+impl<F> ViewFactory for F
+where
+    F: Fn(&RenderSettings, Viewbox, CancelToken) -> Box<dyn View> + Send,
+{
+    fn view(
+        &self,
+        settings: &RenderSettings,
+        position: Viewbox,
+        cancel: CancelToken,
+    ) -> Box<dyn View> {
+        self(settings, position, cancel)
+    }
+}
+
+/// Builds views with the solver picked by `RenderSettings::solver`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SolverKindViews;
+
+impl ViewFactory for SolverKindViews {
+    fn view(
+        &self,
+        settings: &RenderSettings,
+        position: Viewbox,
+        cancel: CancelToken,
+    ) -> Box<dyn View> {
+        view_cancellable(settings, position, cancel)
+    }
+}
+
+/// Paints the frames of a `MandelbrotWorker` from its view.
+pub trait FramePainter: Send {
+    fn paint(&self, view: &dyn View, settings: &RenderSettings) -> RgbImage;
+}
+
+/// Paints with the palette picked by `RenderSettings::palette`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PalettePainter;
+
+impl FramePainter for PalettePainter {
+    fn paint(&self, view: &dyn View, settings: &RenderSettings) -> RgbImage {
+        view.paint(settings.palette, settings.max_i_value())
+    }
+}
+
+/// A color scale paints every frame, whatever the palette is set to.
+impl<C> FramePainter for C
+where
+    C: ColorScale + Send,
+{
+    fn paint(&self, view: &dyn View, settings: &RenderSettings) -> RgbImage {
+        IValuePainter::new(self.clone(), settings.max_i_value()).paint_pixels(
+            view.width(),
+            view.height(),
+            |x, y| view.i_value(x, y),
+        )
+    }
+}
+
+/// A view of `position` solved as set in `settings`, with a tile cache.
+fn view(settings: &RenderSettings, position: Viewbox) -> Box<dyn View> {
+    view_cancellable(settings, position, CancelToken::new())
+}
+
+/// Like `view`, but solve with `cancel`; the view is stale if that is
+/// cancelled before it is solved.
+fn view_cancellable(
+    settings: &RenderSettings,
+    position: Viewbox,
+    cancel: CancelToken,
+) -> Box<dyn View> {
+    let (treshold, iterations, formula) = (2.0, settings.iterations, settings.formula);
+    let vec = || VecSolver::new(treshold, iterations).with_formula(formula);
+    match settings.solver {
+        SolverKind::Vec => cached_view::<_, VecState>(vec(), position, cancel),
+        SolverKind::SharedVec => cached_view::<_, SharedVecState>(vec(), position, cancel),
+        SolverKind::SimdVec => cached_view::<_, SimdVecState>(
+            SimdVecSolver::new(treshold, iterations).with_formula(formula),
+            position,
+            cancel,
+        ),
+        SolverKind::Array => cached_view::<_, ArrayState>(
+            ArraySolver::new(treshold, iterations).with_formula(formula),
+            position,
+            cancel,
+        ),
+    }
+}
+
+/// Paint `position` as set in `settings`, without a view to keep.
+fn export(settings: &RenderSettings, position: Viewbox) -> RgbImage {
+    view(settings, position).paint(settings.palette, settings.max_i_value())
+}
+
+/// Like `export`, with views from `views` painted by `painter`, unless
+/// `cancel` is cancelled first.
+fn export_cancellable(
+    views: &dyn ViewFactory,
+    painter: &dyn FramePainter,
+    settings: &RenderSettings,
+    position: Viewbox,
+    cancel: CancelToken,
+) -> Result<RgbImage, Cancelled> {
+    let view = views.view(settings, position, cancel);
+    if view.is_stale() {
+        return Err(Cancelled);
+    }
+    Ok(painter.paint(view.as_ref(), settings))
+}
+
+/// What a `MandelbrotWorker` can be asked to do; serializable, so actions can
+/// be logged and replayed.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum MAction {
     Resize(i64, i64),
     Pan(i64, i64),
     PanRelative(f64, f64),
     Zoom(f64),
+    /// Zoom by a factor about a pixel of the frame, which stays fixed.
+    ZoomAt(f64, f64, f64),
+    Rotate(f64),
     Reset(i64, i64),
+    /// Move to a position, e.g. the result of a batch of other actions.
+    SetViewbox(Viewbox),
+    SetPalette(Palette),
+    SetIterations(u16),
+    SetSolver(SolverKind),
+    SetFormula(Formula),
+    /// Render the current position at a multiple of its resolution, sent as
+    /// a `Quality::Export` frame, see `MandelbrotWorker::next_export`.
+    RequestHighResExport(i64),
 }
 
+impl MAction {
+    /// Check that the action can be rendered, before it is sent to a worker.
+    /// `MandelbrotWorker::send` checks every action, from replays and remote
+    /// control too.
+    pub fn validate(&self) -> Result<(), InvalidAction> {
+        let invalid = |reason| {
+            Err(InvalidAction {
+                action: *self,
+                reason,
+            })
+        };
+        let size =
+            |w: i64, h: i64| (1..=MAX_FRAME_SIZE).contains(&w) && (1..=MAX_FRAME_SIZE).contains(&h);
+        let factor = |f: f64| f.is_finite() && f > 0.0;
+        match *self {
+            MAction::Resize(w, h) | MAction::Reset(w, h) if !size(w, h) => {
+                invalid("width and height must be from 1 to MAX_FRAME_SIZE")
+            }
+            MAction::SetViewbox(v) if !size(v.width, v.height) => {
+                invalid("width and height must be from 1 to MAX_FRAME_SIZE")
+            }
+            MAction::SetViewbox(v) if !v.is_finite() => invalid("viewbox must be finite"),
+            MAction::Pan(x, y) if x.abs() > MAX_FRAME_SIZE || y.abs() > MAX_FRAME_SIZE => {
+                invalid("pans must be at most MAX_FRAME_SIZE pixels")
+            }
+            MAction::PanRelative(x, y) if !(x.abs() <= 1.0 && y.abs() <= 1.0) => {
+                invalid("relative pans must be at most one frame")
+            }
+            MAction::Zoom(f) if !factor(f) => invalid("zoom factor must be finite and positive"),
+            MAction::ZoomAt(f, x, y) if !(factor(f) && x.is_finite() && y.is_finite()) => {
+                invalid("zoom factor must be finite and positive, about a finite pixel")
+            }
+            MAction::Rotate(angle) if !angle.is_finite() => invalid("angle must be finite"),
+            MAction::SetIterations(iterations) if iterations > MAX_ITERATIONS => {
+                invalid("iterations must be at most i16::MAX")
+            }
+            MAction::RequestHighResExport(scale) if !(1..=MAX_EXPORT_SCALE).contains(&scale) => {
+                invalid("export scale must be from 1 to MAX_EXPORT_SCALE")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Like `validate`, and check the action against `position`, where the
+    /// actions before it lead: an export must be at most `MAX_EXPORT_PIXELS`.
+    pub fn validate_at(&self, position: Option<Viewbox>) -> Result<(), InvalidAction> {
+        self.validate()?;
+        match (*self, position) {
+            (MAction::RequestHighResExport(scale), Some(p))
+                if p.width * p.height * scale * scale > MAX_EXPORT_PIXELS =>
+            {
+                Err(InvalidAction {
+                    action: *self,
+                    reason: "exports must be at most MAX_EXPORT_PIXELS",
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// An action a worker can't render, see `MAction::validate`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InvalidAction {
+    pub action: MAction,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for InvalidAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid action {:?}: {}", self.action, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidAction {}
+
 pub trait ActionQueue {
     fn add(&self, action: MAction);
 }
@@ -168,64 +941,149 @@ impl ActionQueue for SyncActionQueue {
     }
 }
 
+/// Apply a view action to `view`, the way the render thread of a worker moves
+/// its position, or return false for actions that don't move the view.
+fn apply_view_action(view: &mut Option<Viewbox>, action: MAction) -> bool {
+    match action {
+        MAction::Reset(w, h) => *view = Some(Viewbox::initial(w, h)),
+        MAction::Resize(w, h) => {
+            let v = view.get_or_insert_with(|| Viewbox::initial(w, h));
+            v.width = w;
+            v.height = h;
+        }
+        MAction::SetViewbox(position) => *view = Some(position),
+        MAction::Pan(x, y) => view.iter_mut().for_each(|v| v.pan(x, y)),
+        MAction::PanRelative(x, y) => view.iter_mut().for_each(|v| {
+            let nx = (x * v.width as f64).round() as i64;
+            let ny = (y * v.height as f64).round() as i64;
+            v.pan(nx, ny);
+        }),
+        MAction::Zoom(factor) => view.iter_mut().for_each(|v| v.zoom(factor)),
+        MAction::ZoomAt(factor, x, y) => view
+            .iter_mut()
+            .for_each(|v| v.zoom_at(factor, Point::new(x, y))),
+        MAction::Rotate(angle) => view.iter_mut().for_each(|v| v.rotate(angle)),
+        MAction::SetPalette(_)
+        | MAction::SetIterations(_)
+        | MAction::SetSolver(_)
+        | MAction::SetFormula(_)
+        | MAction::RequestHighResExport(_) => return false,
+    }
+    true
+}
+
+/// Combine a batch of actions into as few as possible, with the same result.
+///
+/// `view` follows the position of the worker the actions are for. Each run of
+/// actions that move the view is applied to it in order and sent as a single
+/// `SetViewbox`, so the worker ends up where the actions one by one would have
+/// taken it. Resets, which make a preview, and settings changes are kept, in
+/// order.
+fn batch(view: &mut Option<Viewbox>, messages: Vec<MAction>) -> Vec<MAction> {
+    let mut batched = vec![];
+    let mut start = *view;
+    let flush = |batched: &mut Vec<MAction>, start: Option<Viewbox>, view: Option<Viewbox>| {
+        if let Some(view) = view.filter(|&v| Some(v) != start) {
+            batched.push(MAction::SetViewbox(view));
+        }
+    };
+    for message in messages {
+        if let MAction::Reset(..) = message {
+            apply_view_action(view, message);
+            batched.push(message);
+            start = *view;
+        } else if !apply_view_action(view, message) {
+            flush(&mut batched, start, *view);
+            batched.push(message);
+            start = *view;
+        }
+    }
+    flush(&mut batched, start, *view);
+    batched
+}
+
+/// How long `BatchActionQueue` collects actions before sending them on.
+pub const DEFAULT_BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Collects actions for an interval and sends them on batched, for a worker
+/// whose actions all go through the queue.
 pub struct BatchActionQueue {
     q: Arc<RwLock<Vec<MAction>>>,
+    interval: Arc<Mutex<Duration>>,
+    stop: Option<Sender<()>>,
+    handle: Option<thread::JoinHandle<()>>,
 }
 
 impl BatchActionQueue {
-    fn spawn_q_sender(q: Arc<RwLock<Vec<MAction>>>, tx: Sender<MAction>) -> thread::JoinHandle<()> {
-        thread::spawn(move || loop {
-            thread::sleep(Duration::from_millis(100));
-            let mut resize_x: i64 = 0;
-            let mut resize_y: i64 = 0;
-            let mut pan_x: i64 = 0;
-            let mut pan_y: i64 = 0;
-            let mut pan_rel_x: f64 = 0.0;
-            let mut pan_rel_y: f64 = 0.0;
-            let mut zoom: f64 = 1.0;
-            let messages: Vec<MAction> = std::mem::take(q.write().unwrap().as_mut());
-            for message in messages {
-                match message {
-                    MAction::Resize(x, y) => {
-                        resize_x = x;
-                        resize_y = y;
-                    }
-                    MAction::Pan(x, y) => {
-                        pan_x += x;
-                        pan_y += y;
-                    }
-                    MAction::PanRelative(x, y) => {
-                        pan_rel_x += x;
-                        pan_rel_y += y;
-                    }
-                    MAction::Zoom(f) => {
-                        zoom *= f;
+    fn spawn_q_sender(
+        q: Arc<RwLock<Vec<MAction>>>,
+        interval: Arc<Mutex<Duration>>,
+        tx: Sender<MAction>,
+        stop: Receiver<()>,
+    ) -> thread::JoinHandle<()> {
+        thread::Builder::new()
+            .name("mandelox-batch".to_string())
+            .spawn(move || {
+                let mut view = None;
+                loop {
+                    let interval = *interval.lock().unwrap();
+                    match stop.recv_timeout(interval) {
+                        Err(RecvTimeoutError::Timeout) => (),
+                        _ => return,
                     }
-                    MAction::Reset(_, _) => {
-                        tx.send(message).unwrap();
-                        continue;
+                    // Actions are sent on under the lock, so they are always
+                    // either queued or sent, see `MandelbrotWorker::send`.
+                    let mut q = q.write().unwrap();
+                    for action in batch(&mut view, std::mem::take(q.as_mut())) {
+                        if tx.send(action).is_err() {
+                            return;
+                        }
                     }
                 }
-            }
-            if resize_x != 0 && resize_y != 0 {
-                tx.send(MAction::Resize(resize_x, resize_y)).unwrap();
-            }
-            if pan_x != 0 || pan_y != 0 {
-                tx.send(MAction::Pan(pan_x, pan_y)).unwrap();
-            }
-            if pan_rel_x != 0.0 || pan_rel_y != 0.0 {
-                tx.send(MAction::PanRelative(pan_rel_x, pan_rel_y)).unwrap();
-            }
-            if zoom != 1.0 {
-                tx.send(MAction::Zoom(zoom)).unwrap();
-            }
-        })
+            })
+            .expect("failed to spawn batching thread")
     }
 
     pub fn new(tx: Sender<MAction>) -> Self {
+        Self::with_interval(tx, DEFAULT_BATCH_INTERVAL)
+    }
+
+    pub fn with_interval(tx: Sender<MAction>, interval: Duration) -> Self {
         let q = Arc::new(RwLock::new(vec![]));
-        BatchActionQueue::spawn_q_sender(q.clone(), tx);
-        Self { q }
+        let interval = Arc::new(Mutex::new(interval));
+        let (stop, stop_rx) = channel();
+        let handle = BatchActionQueue::spawn_q_sender(q.clone(), interval.clone(), tx, stop_rx);
+        Self {
+            q,
+            interval,
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+
+    /// Actions waiting for the next batch.
+    fn queued(&self) -> Arc<RwLock<Vec<MAction>>> {
+        self.q.clone()
+    }
+
+    /// Collect actions for `interval` from the next batch on.
+    pub fn set_interval(&self, interval: Duration) {
+        *self.interval.lock().unwrap() = interval;
+    }
+
+    /// Stop the batching thread and wait for it; actions still waiting to be
+    /// sent are dropped. This is also done when the queue is dropped.
+    pub fn shutdown(&mut self) {
+        self.stop = None;
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for BatchActionQueue {
+    fn drop(&mut self) {
+        self.shutdown();
     }
 }
 
@@ -235,149 +1093,1169 @@ impl ActionQueue for BatchActionQueue {
     }
 }
 
+/// What a worker shows, as of the actions sent to it so far.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorkerStatus {
+    /// `None` until the first reset or resize.
+    pub position: Option<Viewbox>,
+    pub settings: RenderSettings,
+    /// Progress of the render in progress, or of the last one.
+    pub progress: Option<ProgressReport>,
+    /// Frames waiting to be taken.
+    pub frames: usize,
+}
+
 pub struct MandelbrotWorker {
-    queue: Box<dyn ActionQueue>,
-    images: Arc<RwLock<Option<RgbImage>>>,
-    shutdown: Arc<AtomicBool>,
+    queue: BatchActionQueue,
+    frames: Arc<Mutex<VecDeque<Frame>>>,
+    exports: Arc<Mutex<VecDeque<Frame>>>,
+    dropped: Arc<AtomicU64>,
+    /// Cancels the render in progress, which any new action supersedes.
+    render: Arc<Mutex<CancelToken>>,
+    progress: Arc<Mutex<Option<Progress>>>,
+    recorder: Mutex<Option<SessionRecorder>>,
+    /// Position and settings the actions sent so far lead to.
+    sent: Mutex<(Option<Viewbox>, RenderSettings)>,
+    /// Cancelled on shutdown, which also cancels an export in progress.
+    shutdown: CancelToken,
+    threads: Vec<thread::JoinHandle<()>>,
 }
 
 impl MandelbrotWorker {
-    fn spawn_receive_images(
-        rx: Receiver<RgbImage>,
-        images: Arc<RwLock<Option<RgbImage>>>,
-        shutdown: Arc<AtomicBool>,
-    ) -> thread::JoinHandle<()> {
-        thread::spawn(move || loop {
-            if shutdown.load(Ordering::SeqCst) {
-                return;
-            }
-            match rx.recv_timeout(Duration::from_millis(20)) {
-                Ok(img) => {
-                    images.write().unwrap().replace(img);
-                }
-                Err(RecvTimeoutError::Timeout) => (),
-                Err(RecvTimeoutError::Disconnected) => return,
-            }
-        })
-    }
-
+    #[allow(clippy::too_many_arguments)]
     fn spawn_mandelbrot(
         rx: Receiver<MAction>,
-        tx: Sender<RgbImage>,
-        shutdown: Arc<AtomicBool>,
+        queued: Arc<RwLock<Vec<MAction>>>,
+        mut frames: FrameSender,
+        mut settings: RenderSettings,
+        views: Box<dyn ViewFactory>,
+        painter: Box<dyn FramePainter>,
+        render: Arc<Mutex<CancelToken>>,
+        progress: Arc<Mutex<Option<Progress>>>,
+        shutdown: CancelToken,
     ) -> thread::JoinHandle<()> {
-        thread::spawn(move || {
-            let mut m: Option<Mandelbrot<defaults::State>> = None;
-            loop {
-                if shutdown.load(Ordering::SeqCst) {
-                    return;
-                }
-                let repaint = match rx.recv_timeout(Duration::from_millis(20)) {
-                    Ok(MAction::Reset(w, h)) => {
-                        m = Some(mandelbrot(w, h));
-                        true
+        thread::Builder::new()
+            .name("mandelox-render".to_string())
+            .spawn(move || {
+                let mut m: Option<Box<dyn View>> = None;
+                // Next action, if it was already sent when a render started.
+                let mut next: Option<MAction> = None;
+                // Token for a new render, unless shutting down. Checked under the
+                // lock, so `shutdown` either sees the new token or is seen here.
+                // Actions sent before it couldn't cancel it, so it is cancelled
+                // right away if any of them is still waiting.
+                let new_render = |next: &mut Option<MAction>| {
+                    let mut current = render.lock().unwrap();
+                    if shutdown.is_cancelled() {
+                        return None;
                     }
-                    Ok(MAction::Resize(w, h)) => {
-                        let m = m.get_or_insert_with(|| mandelbrot(w, h));
-                        m.resize(w, h);
-                        true
+                    *current = CancelToken::new();
+                    let queued = queued.read().unwrap();
+                    if next.is_none() {
+                        *next = rx.try_recv().ok();
                     }
-                    Ok(MAction::Pan(x, y)) => match m {
-                        Some(ref mut m) => {
-                            if x != 0 {
-                                m.pan_fast_vertical(y);
-                            }
-                            if y != 0 {
-                                m.pan_fast_horizontal(x);
+                    if next.is_some() || !queued.is_empty() {
+                        current.cancel();
+                    }
+                    Some(current.clone())
+                };
+                // A new view is solved with the token of its render, so the
+                // actions that supersede it cancel that solve too.
+                let new_view =
+                    |settings: &RenderSettings, position: Viewbox, cancel: &CancelToken| {
+                        let new = views.view(settings, position, cancel.clone());
+                        *progress.lock().unwrap() = new.progress();
+                        new
+                    };
+                let mut cancel = CancelToken::new();
+                loop {
+                    if shutdown.is_cancelled() {
+                        return;
+                    }
+                    let action = match next.take() {
+                        Some(action) => Ok(action),
+                        None => rx.recv_timeout(Duration::from_millis(20)),
+                    };
+                    let started = Instant::now();
+                    if action.is_ok() {
+                        // Actions sent from now on cancel the render of this one.
+                        let Some(token) = new_render(&mut next) else {
+                            return;
+                        };
+                        cancel = token;
+                        if let Some(ref mut m) = m {
+                            m.set_cancel_token(cancel.clone());
+                        }
+                    }
+                    let repaint = match action {
+                        Ok(MAction::Reset(w, h)) => {
+                            let position = Viewbox::initial(w, h);
+                            frames.send_preview(
+                                views.as_ref(),
+                                painter.as_ref(),
+                                &settings,
+                                position,
+                                &cancel,
+                                started,
+                            );
+                            m = Some(new_view(&settings, position, &cancel));
+                            true
+                        }
+                        Ok(MAction::Resize(w, h)) => {
+                            let m = m.get_or_insert_with(|| {
+                                new_view(&settings, Viewbox::initial(w, h), &cancel)
+                            });
+                            m.resize(w, h);
+                            true
+                        }
+                        Ok(MAction::SetViewbox(position)) => {
+                            match m {
+                                Some(ref mut m) => m.set_position_fast(position),
+                                None => m = Some(new_view(&settings, position, &cancel)),
                             }
                             true
                         }
-                        None => false,
-                    },
-                    Ok(MAction::PanRelative(x, y)) => match m {
-                        Some(ref mut m) => {
-                            if y != 0.0 {
-                                m.pan_fast_vertical_relative(y);
+                        Ok(MAction::SetPalette(palette)) => {
+                            settings.palette = palette;
+                            m.is_some()
+                        }
+                        Ok(MAction::SetIterations(iterations)) => {
+                            settings.iterations = iterations;
+                            if let Some(position) = m.as_ref().map(|m| m.position()) {
+                                frames.send_preview(
+                                    views.as_ref(),
+                                    painter.as_ref(),
+                                    &settings,
+                                    position,
+                                    &cancel,
+                                    started,
+                                );
+                                m = Some(new_view(&settings, position, &cancel));
                             }
-                            if x != 0.0 {
-                                m.pan_fast_horizontal_relative(x);
+                            m.is_some()
+                        }
+                        Ok(MAction::SetSolver(solver)) => {
+                            settings.solver = solver;
+                            if let Some(position) = m.as_ref().map(|m| m.position()) {
+                                frames.send_preview(
+                                    views.as_ref(),
+                                    painter.as_ref(),
+                                    &settings,
+                                    position,
+                                    &cancel,
+                                    started,
+                                );
+                                m = Some(new_view(&settings, position, &cancel));
                             }
-                            true
+                            m.is_some()
                         }
-                        None => false,
-                    },
-                    Ok(MAction::Zoom(factor)) => match m {
-                        Some(ref mut m) => {
-                            m.zoom(factor);
-                            true
+                        Ok(MAction::SetFormula(formula)) => {
+                            settings.formula = formula;
+                            if let Some(position) = m.as_ref().map(|m| m.position()) {
+                                frames.send_preview(
+                                    views.as_ref(),
+                                    painter.as_ref(),
+                                    &settings,
+                                    position,
+                                    &cancel,
+                                    started,
+                                );
+                                m = Some(new_view(&settings, position, &cancel));
+                            }
+                            m.is_some()
                         }
-                        None => false,
-                    },
-                    Err(RecvTimeoutError::Timeout) => false,
-                    Err(RecvTimeoutError::Disconnected) => return,
-                };
-                if repaint {
-                    if let Some(ref m) = m {
-                        if tx.send(m.paint(Rainbow, 100)).is_err() {
-                            return;
+                        // Exports aren't cancelled by later actions, only by a
+                        // shutdown, and leave the view as it is.
+                        Ok(MAction::RequestHighResExport(scale)) => {
+                            if let Some(position) = m.as_ref().map(|m| m.position()) {
+                                let position = position.upscaled(scale);
+                                match export_cancellable(
+                                    views.as_ref(),
+                                    painter.as_ref(),
+                                    &settings,
+                                    position,
+                                    shutdown.clone(),
+                                ) {
+                                    Ok(image) => frames.send(
+                                        image,
+                                        position,
+                                        Quality::Export,
+                                        settings,
+                                        started,
+                                    ),
+                                    Err(Cancelled) => return,
+                                }
+                            }
+                            false
+                        }
+                        Ok(MAction::Pan(x, y)) => match m {
+                            Some(ref mut m) => {
+                                m.pan_fast(x, y);
+                                true
+                            }
+                            None => false,
+                        },
+                        Ok(MAction::PanRelative(x, y)) => match m {
+                            Some(ref mut m) => {
+                                m.pan_fast_relative(x, y);
+                                true
+                            }
+                            None => false,
+                        },
+                        Ok(MAction::Zoom(factor)) => match m {
+                            Some(ref mut m) => {
+                                m.zoom_fast(factor);
+                                true
+                            }
+                            None => false,
+                        },
+                        Ok(MAction::ZoomAt(factor, x, y)) => match m {
+                            Some(ref mut m) => {
+                                m.zoom_at(factor, Point::new(x, y));
+                                true
+                            }
+                            None => false,
+                        },
+                        Ok(MAction::Rotate(angle)) => match m {
+                            Some(ref mut m) => {
+                                m.rotate(angle);
+                                true
+                            }
+                            None => false,
+                        },
+                        // A cancelled render that nothing superseded, e.g. because
+                        // the actions cancelled out, still has to be finished.
+                        Err(RecvTimeoutError::Timeout) => match m {
+                            Some(ref mut m) if m.is_stale() => {
+                                let Some(token) = new_render(&mut next) else {
+                                    return;
+                                };
+                                cancel = token;
+                                m.set_cancel_token(cancel.clone());
+                                m.refresh();
+                                true
+                            }
+                            _ => false,
+                        },
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    };
+                    if repaint {
+                        if let Some(m) = m.as_ref().filter(|m| !m.is_stale()) {
+                            let image = painter.paint(m.as_ref(), &settings);
+                            let position = m.position();
+                            frames.send(image, position, Quality::Final, settings, started);
                         }
                     }
                 }
-            }
-        })
+            })
+            .expect("failed to spawn render thread")
     }
 
     pub fn new() -> Self {
+        Self::with_settings(RenderSettings::default())
+    }
+
+    /// Render with `settings` until they are changed by actions, with the
+    /// solver and palette they pick.
+    ///
+    /// Panics if `settings.iterations` is over `MAX_ITERATIONS`.
+    pub fn with_settings(settings: RenderSettings) -> Self {
+        Self::with_views(
+            settings,
+            Box::new(SolverKindViews),
+            Box::new(PalettePainter),
+        )
+    }
+
+    /// Like `with_settings`, but render the views `views` builds, e.g. with a
+    /// solver of our own, and paint them with `painter`.
+    ///
+    /// Panics if `settings.iterations` is over `MAX_ITERATIONS`.
+    pub fn with_views(
+        settings: RenderSettings,
+        views: Box<dyn ViewFactory>,
+        painter: Box<dyn FramePainter>,
+    ) -> Self {
+        assert!(
+            settings.iterations <= MAX_ITERATIONS,
+            "iterations must be at most i16::MAX"
+        );
         let (tx_actions, rx_actions) = channel::<MAction>();
-        let (tx_img, rx_img) = channel::<RgbImage>();
-        let images = Arc::new(RwLock::<Option<RgbImage>>::new(None));
-        let shutdown = Arc::new(AtomicBool::new(false));
+        let frames = Arc::new(Mutex::new(VecDeque::with_capacity(FRAME_QUEUE_CAPACITY)));
+        let exports = Arc::new(Mutex::new(VecDeque::with_capacity(EXPORT_QUEUE_CAPACITY)));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let render = Arc::new(Mutex::new(CancelToken::new()));
+        let progress = Arc::new(Mutex::new(None));
+        let shutdown = CancelToken::new();
 
-        Self::spawn_receive_images(rx_img, images.clone(), shutdown.clone());
-        Self::spawn_mandelbrot(rx_actions, tx_img, shutdown.clone());
+        let queue = BatchActionQueue::new(tx_actions);
+        let sender = FrameSender {
+            frames: frames.clone(),
+            exports: exports.clone(),
+            dropped: dropped.clone(),
+            sequence: 0,
+        };
+        let threads = vec![Self::spawn_mandelbrot(
+            rx_actions,
+            queue.queued(),
+            sender,
+            settings,
+            views,
+            painter,
+            render.clone(),
+            progress.clone(),
+            shutdown.clone(),
+        )];
 
         Self {
-            queue: Box::new(BatchActionQueue::new(tx_actions)),
-            images,
+            queue,
+            frames,
+            exports,
+            dropped,
+            render,
+            progress,
+            recorder: Mutex::new(None),
+            sent: Mutex::new((None, settings)),
             shutdown,
+            threads,
         }
     }
 
-    fn send(&self, action: MAction) {
+    /// Stop rendering and wait for the worker threads, including those of the
+    /// solver, to finish. This is also done when the worker is dropped.
+    pub fn shutdown(&mut self) {
+        {
+            let render = self.render.lock().unwrap();
+            self.shutdown.cancel();
+            render.cancel();
+        }
+        for handle in self.threads.drain(..) {
+            let _ = handle.join();
+        }
+    }
+
+    /// Send any action, e.g. one replayed from a session, or reject it if it
+    /// is invalid. The methods below are shorthands for this, which drop
+    /// invalid actions.
+    pub fn send(&self, action: MAction) -> Result<(), InvalidAction> {
+        // Validated, recorded, followed and queued under one lock, so actions
+        // sent from several threads are checked against the position they
+        // apply to and recorded in the order they are rendered, and a
+        // recording starts from where the actions before it lead.
+        let mut sent = self.sent.lock().unwrap();
+        action.validate_at(sent.0)?;
+        if let Some(ref mut recorder) = *self.recorder.lock().unwrap() {
+            recorder.record(action);
+        }
+        let (ref mut position, ref mut settings) = *sent;
+        if !apply_view_action(position, action) {
+            settings.apply(action);
+        }
+        // Queued under the render lock too, so a render started after the
+        // action was sent either is cancelled here or sees it waiting.
+        let render = self.render.lock().unwrap();
+        render.cancel();
         self.queue.add(action);
+        Ok(())
+    }
+
+    /// Record the actions sent from now on, replacing any recording in
+    /// progress.
+    ///
+    /// The recording starts with the position and settings the actions sent
+    /// so far lead to, so it replays the same on a new worker.
+    pub fn record(&self, mut recorder: SessionRecorder) {
+        // Held until the recorder is in place, so no action is sent meanwhile.
+        let sent = self.sent.lock().unwrap();
+        let (position, settings) = *sent;
+        let mut current = self.recorder.lock().unwrap();
+        if let Some(position) = position {
+            recorder.record(MAction::SetViewbox(position));
+        }
+        recorder.record(MAction::SetPalette(settings.palette));
+        recorder.record(MAction::SetIterations(settings.iterations));
+        recorder.record(MAction::SetSolver(settings.solver));
+        recorder.record(MAction::SetFormula(settings.formula));
+        *current = Some(recorder);
+    }
+
+    /// Stop recording, with the result of writing the session, or `None` if
+    /// there was no recording.
+    pub fn stop_recording(&self) -> Option<std::io::Result<()>> {
+        self.recorder
+            .lock()
+            .unwrap()
+            .take()
+            .map(SessionRecorder::finish)
     }
 
     pub fn reset(&self, width: i64, height: i64) {
-        self.send(MAction::Reset(width, height));
+        let _ = self.send(MAction::Reset(width, height));
     }
 
     pub fn resize(&self, width: i64, height: i64) {
-        self.send(MAction::Resize(width, height));
+        let _ = self.send(MAction::Resize(width, height));
     }
 
     pub fn pan(&self, x: i64, y: i64) {
-        self.send(MAction::Pan(x, y));
+        let _ = self.send(MAction::Pan(x, y));
     }
 
     pub fn pan_relative(&self, x: f64, y: f64) {
-        self.send(MAction::PanRelative(x, y))
+        let _ = self.send(MAction::PanRelative(x, y));
     }
 
     pub fn zoom(&self, factor: f64) {
-        self.send(MAction::Zoom(factor))
+        let _ = self.send(MAction::Zoom(factor));
+    }
+
+    pub fn zoom_at(&self, factor: f64, x: f64, y: f64) {
+        let _ = self.send(MAction::ZoomAt(factor, x, y));
+    }
+
+    pub fn rotate(&self, angle: f64) {
+        let _ = self.send(MAction::Rotate(angle));
+    }
+
+    pub fn set_viewbox(&self, position: Viewbox) {
+        let _ = self.send(MAction::SetViewbox(position));
+    }
+
+    /// Batch actions over `interval` rather than `DEFAULT_BATCH_INTERVAL`.
+    pub fn set_batch_interval(&self, interval: Duration) {
+        self.queue.set_interval(interval);
+    }
+
+    pub fn set_palette(&self, palette: Palette) {
+        let _ = self.send(MAction::SetPalette(palette));
+    }
+
+    pub fn set_iterations(&self, iterations: u16) {
+        let _ = self.send(MAction::SetIterations(iterations));
+    }
+
+    pub fn set_solver(&self, solver: SolverKind) {
+        let _ = self.send(MAction::SetSolver(solver));
+    }
+
+    pub fn set_formula(&self, formula: Formula) {
+        let _ = self.send(MAction::SetFormula(formula));
+    }
+
+    /// Render the current position at `scale` times its resolution, from 1 to
+    /// `MAX_EXPORT_SCALE` and to at most `MAX_EXPORT_PIXELS`; the image comes
+    /// as a frame of `Quality::Export` from `next_export`.
+    pub fn request_export(&self, scale: i64) {
+        let _ = self.send(MAction::RequestHighResExport(scale));
+    }
+
+    /// Progress of the render in progress, or of the last one.
+    pub fn progress(&self) -> Option<ProgressReport> {
+        self.progress.lock().unwrap().as_ref().map(Progress::report)
+    }
+
+    /// Where the actions sent so far lead, which the worker may still be
+    /// rendering.
+    pub fn position(&self) -> Option<Viewbox> {
+        self.sent.lock().unwrap().0
+    }
+
+    pub fn settings(&self) -> RenderSettings {
+        self.sent.lock().unwrap().1
+    }
+
+    pub fn status(&self) -> WorkerStatus {
+        let (position, settings) = *self.sent.lock().unwrap();
+        WorkerStatus {
+            position,
+            settings,
+            progress: self.progress(),
+            frames: self.frames_count(),
+        }
+    }
+
+    /// Frames waiting to be taken, at most `FRAME_QUEUE_CAPACITY`.
+    pub fn frames_count(&self) -> usize {
+        self.frames.lock().unwrap().len()
+    }
+
+    /// Frames and exports dropped so far because their queue was full, i.e.
+    /// not taken in time, e.g. by a consumer that only wants every frame in
+    /// order.
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped.load(Ordering::SeqCst)
+    }
+
+    /// Take the oldest frame waiting, e.g. to record every frame in order.
+    pub fn next_frame(&self) -> Option<Frame> {
+        self.frames.lock().unwrap().pop_front()
     }
 
-    pub fn images_count(&self) -> usize {
-        usize::from(self.images.read().unwrap().is_some())
+    /// Take the newest frame, dropping the older ones, e.g. to display it.
+    pub fn latest_frame(&self) -> Option<Frame> {
+        let mut frames = self.frames.lock().unwrap();
+        let latest = frames.pop_back();
+        frames.clear();
+        latest
     }
 
     pub fn next_image(&self) -> Option<RgbImage> {
-        self.images.write().unwrap().take()
+        self.latest_frame().map(|frame| frame.image)
+    }
+
+    /// Take the oldest export waiting, at most `EXPORT_QUEUE_CAPACITY`; exports
+    /// aren't queued with the frames, so they aren't displayed or dropped with
+    /// them.
+    pub fn next_export(&self) -> Option<Frame> {
+        self.exports.lock().unwrap().pop_front()
     }
 }
 
 impl Drop for MandelbrotWorker {
     fn drop(&mut self) {
-        self.shutdown.store(true, Ordering::SeqCst);
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_same_state(a: &defaults::State, b: &defaults::State) {
+        assert_eq!(MbState::width(a), MbState::width(b));
+        assert_eq!(MbState::height(a), MbState::height(b));
+        for y in 0..MbState::height(a) {
+            for x in 0..MbState::width(a) {
+                assert_eq!(a.i_value(x, y), b.i_value(x, y), "at ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_zoom_fast() {
+        for factor in [2.0, 0.5, 4.0, 0.25] {
+            let mut fast = mandelbrot(60, 40);
+            fast.pan(8, -4);
+            let mut full = mandelbrot(60, 40);
+            full.pan(8, -4);
+            fast.zoom_fast(factor);
+            full.zoom(factor);
+            assert_same_state(&fast.state, &full.state);
+        }
+    }
+
+    #[test]
+    fn test_zoom_fast_huge_factors() {
+        let mut m = mandelbrot(20, 10);
+        m.pan(3, 1);
+        m.zoom_fast(2f64.powi(70));
+        m.zoom_fast(2f64.powi(-70));
+        let mut expected = mandelbrot(20, 10);
+        expected.set_position(m.position);
+        assert_same_state(&m.state, &expected.state);
+    }
+
+    #[test]
+    fn test_fast_paths_rotated() {
+        let mut fast = mandelbrot(60, 40);
+        let mut full = mandelbrot(60, 40);
+        fast.rotate(0.7);
+        full.rotate(0.7);
+        fast.zoom_fast(2.0);
+        full.zoom(2.0);
+        assert_same_state(&fast.state, &full.state);
+        fast.pan_fast(9, -4);
+        full.pan(9, -4);
+        assert_same_state(&fast.state, &full.state);
+    }
+
+    #[test]
+    fn test_cancelled_render_leaves_state_stale() {
+        let mut m = mandelbrot(60, 40);
+        let mut full = mandelbrot(60, 40);
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        m.set_cancel_token(cancel);
+        m.zoom_fast(2.0);
+        assert!(m.is_stale());
+        m.pan_fast(5, -3);
+        m.pan_fast_vertical(2);
+        assert!(m.is_stale());
+
+        m.set_cancel_token(CancelToken::new());
+        m.pan_fast(1, 1);
+        assert!(!m.is_stale());
+        full.zoom(2.0);
+        full.pan(6, 0);
+        assert_same_state(&m.state, &full.state);
+    }
+
+    #[test]
+    fn test_set_position_fast() {
+        let mut fast = mandelbrot(60, 40);
+        let mut full = mandelbrot(60, 40);
+        let mut position = fast.position;
+        for step in 0..4 {
+            match step {
+                0 => position.pan(7, -3),
+                1 => position.zoom(2.0),
+                2 => position.zoom_at(1.5, Point::new(10.0, 5.0)),
+                _ => position.rotate(0.2),
+            }
+            fast.set_position_fast(position);
+            full.set_position(position);
+            assert_eq!(fast.position, position);
+            assert_same_state(&fast.state, &full.state);
+        }
+    }
+
+    #[test]
+    fn test_batch_queue_shutdown() {
+        let (tx, rx) = channel();
+        let mut queue = BatchActionQueue::new(tx);
+        queue.add(MAction::Reset(40, 30));
+        queue.add(MAction::Pan(1, 2));
+        queue.add(MAction::Pan(3, 4));
+        let reset = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(reset, MAction::Reset(40, 30)));
+        let mut expected = Viewbox::initial(40, 30);
+        expected.pan(4, 6);
+        let batched = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(batched, MAction::SetViewbox(v) if v == expected));
+        // The receiver going away must not panic the batching thread.
+        drop(rx);
+        queue.add(MAction::Zoom(2.0));
+        // The batch is taken and sent under the lock, so once it is gone from
+        // the queue the send has failed.
+        while !queue.q.read().unwrap().is_empty() {
+            thread::yield_now();
+        }
+        let handle = queue.handle.take().unwrap();
+        queue.shutdown();
+        assert!(handle.join().is_ok());
+    }
+
+    #[test]
+    fn test_mandelbrot_worker_shutdown() {
+        let mut worker = MandelbrotWorker::new();
+        worker.reset(40, 30);
+        for _ in 0..100 {
+            if worker.next_image().is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        worker.zoom(2.0);
+        worker.shutdown();
+        assert!(worker.threads.is_empty());
+    }
+
+    /// Wait for the worker to paint `expected`, skipping the frames before it.
+    fn wait_for_image(worker: &MandelbrotWorker, expected: &RgbImage) {
+        for _ in 0..400 {
+            if worker.next_image().as_ref() == Some(expected) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(25));
+        }
+        panic!("worker did not paint the expected image");
+    }
+
+    #[test]
+    fn test_mandelbrot_worker_settings() {
+        let worker = MandelbrotWorker::with_settings(RenderSettings {
+            solver: SolverKind::SimdVec,
+            palette: Palette::Greyscale,
+            iterations: 50,
+            ..RenderSettings::default()
+        });
+        worker.reset(40, 30);
+        let simd = Mandelbrot::<SimdVecState>::with_solver(SimdVecSolver::new(2.0, 50), 40, 30);
+        wait_for_image(&worker, &simd.paint(Palette::Greyscale, 50));
+
+        worker.set_palette(Palette::Rainbow);
+        wait_for_image(&worker, &simd.paint(Palette::Rainbow, 50));
+
+        worker.zoom(2.0);
+        worker.set_solver(SolverKind::Array);
+        worker.set_iterations(30);
+        let mut array = Mandelbrot::<ArrayState>::with_solver(ArraySolver::new(2.0, 30), 40, 30);
+        array.zoom(2.0);
+        wait_for_image(&worker, &array.paint(Palette::Rainbow, 30));
+
+        let too_many = MAction::SetIterations(MAX_ITERATIONS + 1);
+        assert_eq!(worker.send(too_many).unwrap_err().action, too_many);
+        assert_eq!(worker.settings().iterations, 30);
+    }
+
+    #[test]
+    fn test_mandelbrot_worker_views() {
+        // Solve with arrays, whatever the solver setting, and paint in greys,
+        // whatever the palette.
+        let views = |settings: &RenderSettings, position, cancel| {
+            let solver = ArraySolver::new(2.0, settings.iterations).with_formula(settings.formula);
+            cached_view::<_, ArrayState>(solver, position, cancel)
+        };
+        let worker = MandelbrotWorker::with_views(
+            RenderSettings::default(),
+            Box::new(views),
+            Box::new(painter::Greyscale),
+        );
+        worker.reset(40, 30);
+        worker.set_solver(SolverKind::SimdVec);
+        worker.set_palette(Palette::Rainbow);
+        worker.set_iterations(50);
+        let array = Mandelbrot::<ArrayState>::with_solver(ArraySolver::new(2.0, 50), 40, 30);
+        wait_for_image(&worker, &array.paint(Palette::Greyscale, 50));
+    }
+
+    #[test]
+    fn test_cancelled_view_is_stale() {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let position = Viewbox::initial(40, 30);
+        let mut cancelled = view_cancellable(&RenderSettings::default(), position, cancel);
+        assert!(cancelled.is_stale());
+        cancelled.set_cancel_token(CancelToken::new());
+        cancelled.refresh();
+        assert!(!cancelled.is_stale());
+        assert_eq!(
+            cancelled.paint(Palette::Rainbow, 100),
+            mandelbrot(40, 30).paint(Palette::Rainbow, 100)
+        );
+    }
+
+    #[test]
+    fn test_mandelbrot_worker_formula_and_export() {
+        let worker = MandelbrotWorker::new();
+        worker.reset(40, 30);
+        let julia = Formula::Julia {
+            re: -0.8,
+            im: 0.156,
+        };
+        worker.set_formula(julia);
+        let solver = VecSolver::default().with_formula(julia);
+        let mut m = Mandelbrot::<VecState>::with_solver(solver, 40, 30);
+        wait_for_image(&worker, &m.paint(Palette::Rainbow, 100));
+
+        worker.request_export(2);
+        let export = (0..400)
+            .find_map(|_| {
+                let frame = worker.next_export();
+                if frame.is_none() {
+                    thread::sleep(Duration::from_millis(25));
+                }
+                frame
+            })
+            .expect("worker did not export");
+        assert_eq!(export.quality, Quality::Export);
+        assert!(std::iter::from_fn(|| worker.next_frame()).all(|f| f.quality != Quality::Export));
+        assert_eq!(export.image.dimensions(), (80, 60));
+        assert_eq!(export.settings.formula, julia);
+        m.set_position(Viewbox::initial(40, 30).upscaled(2));
+        assert_eq!(export.image, m.paint(Palette::Rainbow, 100));
+
+        for scale in [0, -2, MAX_EXPORT_SCALE + 1] {
+            assert!(worker.send(MAction::RequestHighResExport(scale)).is_err());
+        }
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let position = Viewbox::initial(40, 30).upscaled(MAX_EXPORT_SCALE);
+        assert_eq!(
+            export_cancellable(
+                &SolverKindViews,
+                &PalettePainter,
+                &RenderSettings::default(),
+                position,
+                cancel
+            ),
+            Err(Cancelled)
+        );
+    }
+
+    #[test]
+    fn test_maction_validate() {
+        use MAction::*;
+        let mut unsolvable = Viewbox::initial(40, 30);
+        unsolvable.spacing = f64::NAN;
+        let invalid = [
+            Resize(-5, 30),
+            Reset(40, 0),
+            Reset(MAX_FRAME_SIZE + 1, 30),
+            SetViewbox(Viewbox::initial(0, 30)),
+            SetViewbox(unsolvable),
+            Pan(i64::MAX, 0),
+            PanRelative(f64::INFINITY, 0.0),
+            Zoom(0.0),
+            Zoom(f64::NAN),
+            ZoomAt(-2.0, 10.0, 5.0),
+            ZoomAt(2.0, f64::NAN, 5.0),
+            Rotate(f64::INFINITY),
+            SetIterations(MAX_ITERATIONS + 1),
+            RequestHighResExport(0),
+            RequestHighResExport(MAX_EXPORT_SCALE + 1),
+        ];
+        for action in invalid {
+            assert!(action.validate().is_err(), "{:?} is valid", action);
+        }
+        let valid = [
+            Reset(40, 30),
+            SetViewbox(Viewbox::initial(40, 30)),
+            Pan(-40, 30),
+            PanRelative(-0.5, 1.0),
+            Zoom(0.5),
+            ZoomAt(2.0, 10.0, 5.0),
+            Rotate(-1.0),
+            SetIterations(MAX_ITERATIONS),
+            RequestHighResExport(MAX_EXPORT_SCALE),
+        ];
+        for action in valid {
+            assert_eq!(action.validate(), Ok(()));
+        }
+
+        let export = RequestHighResExport(MAX_EXPORT_SCALE);
+        assert_eq!(export.validate_at(None), Ok(()));
+        assert_eq!(
+            export.validate_at(Some(Viewbox::initial(1024, 1024))),
+            Ok(())
+        );
+        let large = Viewbox::initial(MAX_FRAME_SIZE, MAX_FRAME_SIZE);
+        assert!(export.validate_at(Some(large)).is_err());
+        assert!(RequestHighResExport(1).validate_at(Some(large)).is_err());
+        assert!(Zoom(0.0).validate_at(Some(large)).is_err());
+    }
+
+    #[test]
+    fn test_maction_serde() {
+        let mut position = Viewbox::initial(40, 30);
+        position.pan(3, -2);
+        position.rotate(0.5);
+        let actions = vec![
+            MAction::Reset(40, 30),
+            MAction::ZoomAt(2.0, 10.0, 5.5),
+            MAction::SetViewbox(position),
+            MAction::SetPalette(Palette::Greyscale),
+            MAction::SetIterations(250),
+            MAction::SetSolver(SolverKind::SimdVec),
+            MAction::SetFormula(Formula::Julia {
+                re: -0.8,
+                im: 0.156,
+            }),
+            MAction::RequestHighResExport(4),
+        ];
+        let json = serde_json::to_string(&actions).unwrap();
+        let parsed: Vec<MAction> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, actions);
+    }
+
+    fn wait_for_frame(worker: &MandelbrotWorker) -> Frame {
+        for _ in 0..400 {
+            if let Some(frame) = worker.next_frame() {
+                return frame;
+            }
+            thread::sleep(Duration::from_millis(25));
+        }
+        panic!("worker did not paint a frame");
+    }
+
+    #[test]
+    fn test_preview() {
+        let settings = RenderSettings {
+            solver: SolverKind::Array,
+            ..RenderSettings::default()
+        };
+        let position = Viewbox::initial(80, 60);
+        let cancel = CancelToken::new();
+        let image = preview(
+            &SolverKindViews,
+            &PalettePainter,
+            &settings,
+            position,
+            &cancel,
+        );
+        let mut small = Mandelbrot::<ArrayState>::with_solver(ArraySolver::new(2.0, 100), 20, 15);
+        small.set_position(position.downscaled(PREVIEW_SCALE));
+        let expected = imageops::resize(
+            &small.paint(Palette::Rainbow, 100),
+            80,
+            60,
+            FilterType::Nearest,
+        );
+        assert_eq!(image, Some(expected));
+
+        // Expensive previews are skipped rather than solved, as are
+        // cancelled ones.
+        let huge = RenderSettings {
+            iterations: MAX_ITERATIONS,
+            ..settings
+        };
+        let large = Viewbox::initial(MAX_FRAME_SIZE, MAX_FRAME_SIZE);
+        let start = Instant::now();
+        assert_eq!(
+            preview(&SolverKindViews, &PalettePainter, &huge, large, &cancel),
+            None
+        );
+        assert!(start.elapsed() < Duration::from_secs(1));
+        cancel.cancel();
+        assert_eq!(
+            preview(
+                &SolverKindViews,
+                &PalettePainter,
+                &settings,
+                position,
+                &cancel
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_mandelbrot_worker_frames() {
+        let worker = MandelbrotWorker::new();
+        worker.reset(40, 30);
+        let preview = wait_for_frame(&worker);
+        assert_eq!(preview.quality, Quality::Preview);
+        assert_eq!(preview.sequence, 0);
+        assert_eq!(preview.image.dimensions(), (40, 30));
+        let last = wait_for_frame(&worker);
+        assert_eq!(last.quality, Quality::Final);
+        assert_eq!(last.sequence, 1);
+        assert_eq!(last.position, Viewbox::initial(40, 30));
+        assert_eq!(last.settings, RenderSettings::default());
+        assert!(last.duration >= preview.duration);
+        assert_eq!(last.image, mandelbrot(40, 30).paint(Palette::Rainbow, 100));
+
+        // Frames queue up in order, rather than replacing one another.
+        worker.pan(5, 0);
+        thread::sleep(Duration::from_millis(300));
+        worker.pan(0, 5);
+        let mut position = last.position;
+        for (sequence, pan) in [(2, (5, 0)), (3, (0, 5))] {
+            let frame = wait_for_frame(&worker);
+            position.pan(pan.0, pan.1);
+            assert_eq!(frame.sequence, sequence);
+            assert_eq!(frame.quality, Quality::Final);
+            assert_eq!(frame.position, position);
+        }
+        assert_eq!(worker.frames_count(), 0);
+        assert_eq!(worker.dropped_frames(), 0);
+    }
+
+    #[test]
+    fn test_frame_queue_drops_oldest() {
+        let mut sender = FrameSender {
+            frames: Arc::new(Mutex::new(VecDeque::new())),
+            exports: Arc::new(Mutex::new(VecDeque::new())),
+            dropped: Arc::new(AtomicU64::new(0)),
+            sequence: 0,
+        };
+        let position = Viewbox::initial(4, 3);
+        for _ in 0..FRAME_QUEUE_CAPACITY + 3 {
+            let image = RgbImage::new(4, 3);
+            let settings = RenderSettings::default();
+            sender.send(image, position, Quality::Final, settings, Instant::now());
+        }
+        let image = RgbImage::new(8, 6);
+        let settings = RenderSettings::default();
+        sender.send(image, position, Quality::Export, settings, Instant::now());
+        let frames = sender.frames.lock().unwrap();
+        assert_eq!(frames.len(), FRAME_QUEUE_CAPACITY);
+        assert_eq!(frames.front().map(|f| f.sequence), Some(3));
+        assert_eq!(sender.dropped.load(Ordering::SeqCst), 3);
+        // Exports are queued apart, and don't push frames out.
+        let exports = sender.exports.lock().unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports.front().map(|f| f.sequence), Some(19));
+    }
+
+    /// Apply actions one by one, as the render thread would without batching.
+    fn unbatched(view: &mut Option<Viewbox>, actions: &[MAction]) {
+        for &action in actions {
+            apply_view_action(view, action);
+        }
+    }
+
+    #[test]
+    fn test_batch_keeps_order() {
+        use MAction::*;
+        let zoom_then_pan = [Reset(40, 30), Zoom(2.0), Pan(10, 0)];
+        let pan_then_zoom = [Reset(40, 30), Pan(10, 0), Zoom(2.0)];
+        let (mut a, mut b) = (None, None);
+        let batched_a = batch(&mut a, zoom_then_pan.to_vec());
+        batch(&mut b, pan_then_zoom.to_vec());
+        assert_ne!(a, b);
+        let (mut expected_a, mut expected_b) = (None, None);
+        unbatched(&mut expected_a, &zoom_then_pan);
+        unbatched(&mut expected_b, &pan_then_zoom);
+        assert_eq!(a, expected_a);
+        assert_eq!(b, expected_b);
+        assert_eq!(batched_a.len(), 2);
+        assert!(matches!(batched_a[1], SetViewbox(v) if Some(v) == expected_a));
+
+        // Actions before a reset are dropped, not sent after it.
+        let mut view = a;
+        let batched = batch(&mut view, vec![Pan(5, 0), Reset(20, 10), Pan(0, 3)]);
+        let mut expected = Viewbox::initial(20, 10);
+        expected.pan(0, 3);
+        assert_eq!(batched.len(), 2);
+        assert!(matches!(batched[0], Reset(20, 10)));
+        assert!(matches!(batched[1], SetViewbox(v) if v == expected));
+
+        // Settings changes stay in place, and moves that cancel out are dropped.
+        let batched = batch(
+            &mut view,
+            vec![
+                Pan(1, 0),
+                Pan(-1, 0),
+                SetIterations(50),
+                Zoom(0.5),
+                SetPalette(Palette::Greyscale),
+            ],
+        );
+        assert_eq!(batched.len(), 3);
+        assert!(matches!(batched[0], SetIterations(50)));
+        assert!(matches!(batched[1], SetViewbox(_)));
+        assert!(matches!(batched[2], SetPalette(Palette::Greyscale)));
+    }
+
+    #[test]
+    fn test_batch_matches_unbatched() {
+        use MAction::*;
+        let actions = [
+            Pan(3, 4),
+            Resize(50, 40),
+            Zoom(2.0),
+            Pan(-7, 2),
+            ZoomAt(1.5, 10.0, 20.0),
+            Rotate(0.3),
+            PanRelative(0.1, -0.05),
+            SetIterations(80),
+            ZoomAt(0.7, 12.0, 3.0),
+            Zoom(0.5),
+            Pan(11, -9),
+            Resize(30, 60),
+            Reset(40, 30),
+            Rotate(-0.2),
+            SetSolver(SolverKind::Array),
+            PanRelative(-0.3, 0.25),
+            ZoomAt(2.0, 0.0, 0.0),
+            Pan(4, 4),
+        ];
+        for size in [1, 2, 3, 5, 8, actions.len()] {
+            let mut expected = None;
+            let mut mirror = None;
+            let mut batched_view = None;
+            for chunk in actions.chunks(size) {
+                unbatched(&mut expected, chunk);
+                let batched = batch(&mut mirror, chunk.to_vec());
+                assert!(batched.len() <= chunk.len());
+                unbatched(&mut batched_view, &batched);
+                assert_eq!(mirror, expected);
+                assert_eq!(batched_view, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_batched_worker_matches_unbatched() {
+        let worker = MandelbrotWorker::new();
+        worker.set_batch_interval(Duration::from_millis(10));
+        worker.reset(40, 30);
+        let mut m = mandelbrot(40, 30);
+        wait_for_image(&worker, &m.paint(Palette::Rainbow, 100));
+
+        // Sent in one batch: zoom first, then pan in the zoomed frame.
+        worker.set_batch_interval(Duration::from_millis(300));
+        worker.zoom(2.0);
+        worker.pan(10, -5);
+        worker.rotate(0.4);
+        worker.pan_relative(0.25, 0.0);
+        m.zoom(2.0);
+        m.pan(10, -5);
+        m.rotate(0.4);
+        m.pan_relative(0.25, 0.0);
+        wait_for_image(&worker, &m.paint(Palette::Rainbow, 100));
+    }
+
+    /// Deterministic pseudo-random pans, some of them larger than the viewport.
+    fn random_pans(seed: u64, n: usize, w: i64, h: i64) -> Vec<(usize, i64, i64)> {
+        let mut x = seed;
+        let mut next = move |m: i64| {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((x >> 33) as i64).rem_euclid(m)
+        };
+        (0..n)
+            .map(|_| {
+                (
+                    next(3) as usize,
+                    next(2 * w + 3) - w - 1,
+                    next(2 * h + 3) - h - 1,
+                )
+            })
+            .collect()
+    }
+
+    fn check_fast_pans<T, S>()
+    where
+        T: D2ArrayLike + MbState + Split + Join + Send + 'static,
+        S: Solver<T> + Default + Clone + Send + 'static,
+    {
+        let (w, h) = (23, 17);
+        for seed in 0..8 {
+            let mut fast = Mandelbrot::<T>::initialize::<S>(w, h);
+            let mut full = Mandelbrot::<T>::initialize::<S>(w, h);
+            for (kind, x, y) in random_pans(seed, 12, w, h) {
+                match kind {
+                    0 => fast.pan_fast(x, y),
+                    1 => fast.pan_fast_vertical(y),
+                    _ => fast.pan_fast_horizontal(x),
+                }
+                match kind {
+                    0 => full.pan(x, y),
+                    1 => full.pan(0, y),
+                    _ => full.pan(x, 0),
+                }
+                // Pans past the whole view, too, keep its size.
+                assert_eq!(MbState::width(&fast.state), w as usize);
+                assert_eq!(MbState::height(&fast.state), h as usize);
+                for py in 0..h as usize {
+                    for px in 0..w as usize {
+                        assert_eq!(fast.state.i_value(px, py), full.state.i_value(px, py));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_fast_pans_all_states() {
+        use crate::solver::{
+            ArraySolver, ArrayState, SharedVecState, SimdVecSolver, SimdVecState, VecSolver,
+        };
+        check_fast_pans::<defaults::State, defaults::Solver>();
+        check_fast_pans::<SharedVecState, VecSolver>();
+        check_fast_pans::<ArrayState, ArraySolver>();
+        check_fast_pans::<SimdVecState, SimdVecSolver>();
+    }
+
+    #[test]
+    fn test_pan_fast() {
+        let mut fast = mandelbrot(60, 40);
+        let mut full = mandelbrot(60, 40);
+        for (x, y) in [
+            (5, 3),
+            (-7, 0),
+            (0, -11),
+            (-13, 17),
+            (59, -39),
+            (60, 2),
+            (3, -45),
+        ] {
+            fast.pan_fast(x, y);
+            full.pan(x, y);
+            assert_same_state(&fast.state, &full.state);
+        }
     }
 }
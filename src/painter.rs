@@ -1,6 +1,7 @@
 use std::fmt::Debug;
 
 use image::{Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
 
 use crate::solver::MbState;
 
@@ -33,21 +34,20 @@ where
     pub fn new(color: C, max_i_value: i16) -> Self {
         Self { color, max_i_value }
     }
-}
 
-impl<T, C> Painter<T> for IValuePainter<C>
-where
-    C: ColorScale,
-    T: MbState,
-{
-    fn paint(&self, t: &T) -> RgbImage {
-        let width: u32 = t.width().try_into().unwrap();
-        let height: u32 = t.height().try_into().unwrap();
+    /// Paint a `width` by `height` image from the iteration counts `i_value`
+    /// gives for each pixel.
+    pub fn paint_pixels<F>(&self, width: usize, height: usize, i_value: F) -> RgbImage
+    where
+        F: Fn(usize, usize) -> i16,
+    {
+        let width: u32 = width.try_into().unwrap();
+        let height: u32 = height.try_into().unwrap();
 
         let mut img = RgbImage::new(width, height);
         for y in 0..height {
             for x in 0..width {
-                let i_value = t.i_value(x as usize, y as usize);
+                let i_value = i_value(x as usize, y as usize);
                 let color = if i_value == -1 {
                     Rgb([0, 0, 0])
                 } else {
@@ -63,6 +63,16 @@ where
     }
 }
 
+impl<T, C> Painter<T> for IValuePainter<C>
+where
+    C: ColorScale,
+    T: MbState,
+{
+    fn paint(&self, t: &T) -> RgbImage {
+        self.paint_pixels(t.width(), t.height(), |x, y| t.i_value(x, y))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Greyscale;
 
@@ -111,3 +121,20 @@ impl ColorScale for Rainbow {
         Rgb([r, g, b])
     }
 }
+
+/// The color scales, as one that can be picked at runtime.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Palette {
+    #[default]
+    Rainbow,
+    Greyscale,
+}
+
+impl ColorScale for Palette {
+    fn get_color(&self, frac: f64) -> Rgb<u8> {
+        match self {
+            Self::Rainbow => Rainbow.get_color(frac),
+            Self::Greyscale => Greyscale.get_color(frac),
+        }
+    }
+}
@@ -0,0 +1,422 @@
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{MAction, MandelbrotWorker, WorkerStatus};
+
+/// How often connections and the listener check for a shutdown.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Longest request line, without its newline; longer ones get an error and
+/// are skipped rather than read into memory.
+pub const MAX_LINE: usize = 1 << 16;
+
+/// What a client asks of the worker.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum Command {
+    /// Send an action, as `MandelbrotWorker::send` does.
+    Action(MAction),
+    Status,
+}
+
+/// A command, with an id for the client to match it with its response.
+///
+/// On the wire, one JSON object per line, e.g.
+/// `{"id": 1, "method": "action", "params": {"Pan": [10, 0]}}` or
+/// `{"id": 2, "method": "status"}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    #[serde(default)]
+    pub id: Value,
+    #[serde(flatten)]
+    pub command: Command,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// The status of the worker once the command is sent.
+    Result(WorkerStatus),
+    Error(String),
+}
+
+/// The answer to a request, with its id, or a null id if the request could
+/// not be read.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub id: Value,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+/// Run one request line on `worker`. Invalid actions aren't sent, and get
+/// an error.
+pub fn handle(worker: &MandelbrotWorker, line: &str) -> Response {
+    match serde_json::from_str::<Request>(line) {
+        Ok(request) => {
+            let sent = match request.command {
+                Command::Action(action) => worker.send(action),
+                Command::Status => Ok(()),
+            };
+            let outcome = match sent {
+                Ok(()) => Outcome::Result(worker.status()),
+                Err(invalid) => Outcome::Error(invalid.to_string()),
+            };
+            Response {
+                id: request.id,
+                outcome,
+            }
+        }
+        Err(error) => Response {
+            id: Value::Null,
+            outcome: Outcome::Error(error.to_string()),
+        },
+    }
+}
+
+/// Answer the requests read from `reader` on `writer`, one line each, until
+/// the reader ends or `shutdown` is set.
+///
+/// Reads that time out, as set up on sockets by `RemoteControl`, only check
+/// for a shutdown; the line read so far is kept. Lines over `MAX_LINE` get an
+/// error with a null id.
+pub fn serve<R, W>(
+    worker: &MandelbrotWorker,
+    mut reader: R,
+    mut writer: W,
+    shutdown: &AtomicBool,
+) -> io::Result<()>
+where
+    R: BufRead,
+    W: Write,
+{
+    let mut respond = |response: &Response| -> io::Result<()> {
+        serde_json::to_writer(&mut writer, response)?;
+        writeln!(writer)?;
+        writer.flush()
+    };
+    let mut line = vec![];
+    // Whether the rest of an oversized line is being read, to be dropped.
+    let mut oversized = false;
+    while !shutdown.load(Ordering::SeqCst) {
+        // One byte more than a line may have, its newline.
+        let limit = (MAX_LINE + 1 - line.len()) as u64;
+        let ended = match reader.by_ref().take(limit).read_until(b'\n', &mut line) {
+            Ok(0) => true,
+            Ok(_) => !line.ends_with(b"\n") && line.len() <= MAX_LINE,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if line.len() > MAX_LINE && !line.ends_with(b"\n") {
+            if !oversized {
+                respond(&Response {
+                    id: Value::Null,
+                    outcome: Outcome::Error(format!("request is over {} bytes", MAX_LINE)),
+                })?;
+                oversized = true;
+            }
+            line.clear();
+            continue;
+        }
+        let request = String::from_utf8_lossy(&line);
+        if !oversized && !request.trim().is_empty() {
+            respond(&handle(worker, &request))?;
+        }
+        oversized = false;
+        line.clear();
+        if ended {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// A connection to the control socket.
+trait Connection: Read + Write + Send + Sized + 'static {
+    fn try_clone(&self) -> io::Result<Self>;
+    /// Block on reads, but only for `POLL_INTERVAL` at a time.
+    fn poll_reads(&self) -> io::Result<()>;
+}
+
+impl Connection for TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        TcpStream::try_clone(self)
+    }
+    fn poll_reads(&self) -> io::Result<()> {
+        self.set_nonblocking(false)?;
+        self.set_read_timeout(Some(POLL_INTERVAL))
+    }
+}
+
+#[cfg(unix)]
+impl Connection for UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        UnixStream::try_clone(self)
+    }
+    fn poll_reads(&self) -> io::Result<()> {
+        self.set_nonblocking(false)?;
+        self.set_read_timeout(Some(POLL_INTERVAL))
+    }
+}
+
+/// Control of a worker over a local socket, e.g. from a script or another
+/// viewer, with the protocol of `Request` and `Response`.
+///
+/// Each connection is served on a thread of its own; commands from all of
+/// them go to the same worker.
+pub struct RemoteControl {
+    local_addr: Option<SocketAddr>,
+    #[cfg(unix)]
+    path: Option<PathBuf>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl RemoteControl {
+    /// Listen on a TCP address, which should be a local one: there is no
+    /// authentication. Port 0 picks a free port, see `local_addr`.
+    pub fn bind_tcp<A: ToSocketAddrs>(worker: Arc<MandelbrotWorker>, addr: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let mut control = Self::spawn(worker, move || listener.accept().map(|(s, _)| s))?;
+        control.local_addr = Some(local_addr);
+        Ok(control)
+    }
+
+    /// Listen on a Unix domain socket at `path`, which is removed on shutdown.
+    #[cfg(unix)]
+    pub fn bind_unix<P: AsRef<Path>>(worker: Arc<MandelbrotWorker>, path: P) -> io::Result<Self> {
+        let listener = UnixListener::bind(path.as_ref())?;
+        listener.set_nonblocking(true)?;
+        let mut control = Self::spawn(worker, move || listener.accept().map(|(s, _)| s))?;
+        control.path = Some(path.as_ref().to_path_buf());
+        Ok(control)
+    }
+
+    fn spawn<C, F>(worker: Arc<MandelbrotWorker>, mut accept: F) -> io::Result<Self>
+    where
+        C: Connection,
+        F: FnMut() -> io::Result<C> + Send + 'static,
+    {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let stop = shutdown.clone();
+        let handle = thread::Builder::new()
+            .name("mandelox-remote".to_string())
+            .spawn(move || {
+                let mut connections = vec![];
+                while !stop.load(Ordering::SeqCst) {
+                    let connection = match accept() {
+                        Ok(connection) => connection,
+                        // No client waiting, or one that failed to connect.
+                        Err(_) => {
+                            thread::sleep(POLL_INTERVAL);
+                            continue;
+                        }
+                    };
+                    let (worker, stop) = (worker.clone(), stop.clone());
+                    let spawned = thread::Builder::new()
+                        .name("mandelox-remote-conn".to_string())
+                        .spawn(move || {
+                            // A client that goes away only ends its own connection.
+                            let _ = connection.poll_reads().and_then(|()| {
+                                let reader = BufReader::new(connection.try_clone()?);
+                                serve(&worker, reader, connection, &stop)
+                            });
+                        });
+                    // Without a thread, the connection is dropped, i.e. closed.
+                    if let Ok(handle) = spawned {
+                        connections.push(handle);
+                    }
+                    connections.retain(|c: &thread::JoinHandle<()>| !c.is_finished());
+                }
+                for connection in connections {
+                    let _ = connection.join();
+                }
+            })?;
+        Ok(Self {
+            local_addr: None,
+            #[cfg(unix)]
+            path: None,
+            shutdown,
+            handle: Some(handle),
+        })
+    }
+
+    /// The address listened on, for a TCP socket.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Close the socket and its connections. This is also done when the
+    /// control is dropped.
+    pub fn shutdown(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+            #[cfg(unix)]
+            if let Some(ref path) = self.path {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+}
+
+impl Drop for RemoteControl {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::coord::Viewbox;
+    use crate::painter::Palette;
+
+    fn result(response: &Response) -> WorkerStatus {
+        match response.outcome {
+            Outcome::Result(status) => status,
+            Outcome::Error(ref e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn test_serve() {
+        let worker = MandelbrotWorker::new();
+        let requests = [
+            r#"{"id": 1, "method": "status"}"#,
+            r#"{"id": 2, "method": "action", "params": {"Reset": [40, 30]}}"#,
+            "",
+            r#"{"id": "pan", "method": "action", "params": {"Pan": [10, -4]}}"#,
+            r#"{"id": 4, "method": "action", "params": {"SetPalette": "Greyscale"}}"#,
+            r#"{"id": 5, "method": "zoom"}"#,
+            r#"{"id": 6, "method": "action", "params": {"SetIterations": 40000}}"#,
+            r#"{"id": 7, "method": "action", "params": {"RequestHighResExport": 0}}"#,
+            r#"{"id": 8, "method": "action", "params": {"Resize": [-5, 30]}}"#,
+            r#"{"method": "status"}"#,
+        ]
+        .join("\n");
+        let mut output = vec![];
+        serve(
+            &worker,
+            requests.as_bytes(),
+            &mut output,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        let responses: Vec<Response> = output
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 9);
+
+        assert_eq!(responses[0].id, Value::from(1));
+        assert_eq!(result(&responses[0]).position, None);
+        assert_eq!(
+            result(&responses[1]).position,
+            Some(Viewbox::initial(40, 30))
+        );
+        let mut panned = Viewbox::initial(40, 30);
+        panned.pan(10, -4);
+        assert_eq!(responses[2].id, Value::from("pan"));
+        assert_eq!(result(&responses[2]).position, Some(panned));
+        assert_eq!(result(&responses[3]).settings.palette, Palette::Greyscale);
+        assert_eq!(responses[4].id, Value::Null);
+        assert!(matches!(responses[4].outcome, Outcome::Error(_)));
+        for (response, id) in responses[5..8].iter().zip(6..) {
+            assert_eq!(response.id, Value::from(id));
+            assert!(matches!(response.outcome, Outcome::Error(_)));
+        }
+        assert_eq!(responses[8].id, Value::Null);
+        assert_eq!(result(&responses[8]).position, Some(panned));
+        assert_eq!(worker.position(), Some(panned));
+    }
+
+    #[test]
+    fn test_serve_long_lines() {
+        let worker = MandelbrotWorker::new();
+        let long = format!(
+            r#"{{"id": 1, "method": "status", "padding": "{}"}}"#,
+            "x".repeat(2 * MAX_LINE)
+        );
+        let requests = [
+            long.as_str(),
+            r#"{"id": 2, "method": "status"}"#,
+            &" ".repeat(MAX_LINE + 1),
+        ]
+        .join("\n");
+        let mut output = vec![];
+        serve(
+            &worker,
+            requests.as_bytes(),
+            &mut output,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        let responses: Vec<Response> = output
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 3);
+        for i in [0, 2] {
+            assert_eq!(responses[i].id, Value::Null);
+            assert!(matches!(responses[i].outcome, Outcome::Error(_)));
+        }
+        assert_eq!(responses[1].id, Value::from(2));
+        assert_eq!(result(&responses[1]).position, None);
+    }
+
+    fn check_connection<C: Connection>(connection: C) {
+        let mut reader = BufReader::new(connection.try_clone().unwrap());
+        let mut writer = connection;
+        let mut request = |request: &str| {
+            writeln!(writer, "{}", request).unwrap();
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            serde_json::from_str::<Response>(&line).unwrap()
+        };
+        let response = request(r#"{"id": 7, "method": "action", "params": {"Reset": [20, 10]}}"#);
+        assert_eq!(response.id, Value::from(7));
+        assert_eq!(result(&response).position, Some(Viewbox::initial(20, 10)));
+        let response = request(r#"{"id": 8, "method": "status"}"#);
+        assert_eq!(result(&response).position, Some(Viewbox::initial(20, 10)));
+    }
+
+    #[test]
+    fn test_remote_control_tcp() {
+        let worker = Arc::new(MandelbrotWorker::new());
+        let mut control = RemoteControl::bind_tcp(worker.clone(), "127.0.0.1:0").unwrap();
+        let addr = control.local_addr().unwrap();
+        check_connection(TcpStream::connect(addr).unwrap());
+        // Open connections don't hold up the shutdown.
+        let _idle = TcpStream::connect(addr).unwrap();
+        control.shutdown();
+        assert_eq!(worker.position(), Some(Viewbox::initial(20, 10)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_remote_control_unix() {
+        let path = std::env::temp_dir().join(format!("mandelox-{}.sock", std::process::id()));
+        let worker = Arc::new(MandelbrotWorker::new());
+        let control = RemoteControl::bind_unix(worker, &path).unwrap();
+        check_connection(UnixStream::connect(&path).unwrap());
+        drop(control);
+        assert!(!path.exists());
+    }
+}
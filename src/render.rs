@@ -0,0 +1,253 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::thread;
+
+use crate::coord::Viewbox;
+use crate::solver::MbState;
+use crate::threads::{
+    oneshot, CancelToken, Cancelled, Join, OneshotReceiver, OneshotSender, Split,
+};
+use crate::Mandelbrot;
+
+/// The solved state of a render, or `Err(Cancelled)` if it was cancelled or
+/// the renderer shut down first.
+pub type RenderResult<T> = Result<T, Cancelled>;
+
+struct RenderJob<T> {
+    position: Viewbox,
+    cancel: CancelToken,
+    result: OneshotSender<RenderResult<T>>,
+}
+
+/// Renders in the background for async code, without tying it to an executor.
+///
+/// The `Mandelbrot` lives on a thread of its own, which renders requests in
+/// order; each request returns a `Render` future, completed by that thread.
+pub struct AsyncRenderer<T> {
+    queue: Option<Sender<RenderJob<T>>>,
+    handle: Option<thread::JoinHandle<()>>,
+    /// Token of the render in progress, cancelled on shutdown.
+    current: Arc<Mutex<CancelToken>>,
+    shutdown: CancelToken,
+}
+
+impl<T> AsyncRenderer<T>
+where
+    T: MbState + Split + Join + Clone + Send + 'static,
+{
+    /// Spawn the render thread, with the `Mandelbrot` made by `init`.
+    pub fn spawn<F>(init: F) -> Self
+    where
+        F: FnOnce() -> Mandelbrot<T> + Send + 'static,
+    {
+        let (tx, rx) = channel::<RenderJob<T>>();
+        let current = Arc::new(Mutex::new(CancelToken::new()));
+        let shutdown = CancelToken::new();
+        let (thread_current, thread_shutdown) = (current.clone(), shutdown.clone());
+        let handle = thread::Builder::new()
+            .name("mandelox-async-render".to_string())
+            .spawn(move || {
+                let mut mandelbrot = init();
+                for job in rx {
+                    if job.cancel.is_cancelled() || job.result.is_closed() {
+                        continue;
+                    }
+                    {
+                        // Checked under the lock, so `shutdown` either sees
+                        // this job's token or stops the thread before it runs.
+                        let mut current = thread_current.lock().unwrap();
+                        if thread_shutdown.is_cancelled() {
+                            // Dropping the queue completes the pending
+                            // futures with `Err(Cancelled)`.
+                            break;
+                        }
+                        *current = job.cancel.clone();
+                    }
+                    mandelbrot.set_cancel_token(job.cancel);
+                    mandelbrot.set_position(job.position);
+                    job.result.send(if mandelbrot.is_stale() {
+                        Err(Cancelled)
+                    } else {
+                        Ok(mandelbrot.state.clone())
+                    });
+                }
+            })
+            .expect("failed to spawn render thread");
+        Self {
+            queue: Some(tx),
+            handle: Some(handle),
+            current,
+            shutdown,
+        }
+    }
+
+    /// Render `position` once the requests before it are done.
+    ///
+    /// Dropping the returned future cancels the render.
+    pub fn render(&self, position: Viewbox) -> Render<T> {
+        let (tx, rx) = oneshot();
+        let cancel = CancelToken::new();
+        let job = RenderJob {
+            position,
+            cancel: cancel.clone(),
+            result: tx,
+        };
+        // If the render thread is gone, the job and its sender are dropped,
+        // so the future completes with `Err(Cancelled)`.
+        if let Some(ref queue) = self.queue {
+            let _ = queue.send(job);
+        }
+        Render { result: rx, cancel }
+    }
+}
+
+impl<T> AsyncRenderer<T> {
+    /// Cancel the requests already made and stop the render thread.
+    ///
+    /// Their futures complete with `Err(Cancelled)`.
+    pub fn shutdown(&mut self) {
+        {
+            let current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+            self.shutdown.cancel();
+            current.cancel();
+        }
+        self.queue.take();
+        if let Some(handle) = self.handle.take() {
+            // A panicked render thread already failed its futures; don't
+            // panic again, this runs from `drop`.
+            let _ = handle.join();
+        }
+    }
+}
+
+impl<T> Drop for AsyncRenderer<T> {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Future of a render requested from an `AsyncRenderer`.
+pub struct Render<T> {
+    result: OneshotReceiver<RenderResult<T>>,
+    cancel: CancelToken,
+}
+
+impl<T> Render<T> {
+    /// Cancel the render, without waiting for the future to be dropped.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+}
+
+impl<T> Future for Render<T> {
+    type Output = RenderResult<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.result)
+            .poll(cx)
+            .map(|result| result.and_then(|r| r))
+    }
+}
+
+impl<T> Drop for Render<T> {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{defaults, mandelbrot};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Minimal executor: poll `future` on this thread until it is ready.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Arc::new(ThreadWaker(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_async_render() {
+        let renderer = AsyncRenderer::spawn(|| mandelbrot(60, 40));
+        let mut position = Viewbox::initial(60, 40);
+        position.zoom(2.0);
+        let expected = {
+            let mut m = mandelbrot(60, 40);
+            m.set_position(position);
+            m.state
+        };
+
+        let rendered: defaults::State = block_on(renderer.render(position)).unwrap();
+        for y in 0..40 {
+            for x in 0..60 {
+                assert_eq!(rendered.i_value(x, y), expected.i_value(x, y));
+            }
+        }
+
+        // Dropped and cancelled renders don't hold up the ones after them.
+        drop(renderer.render(Viewbox::initial(2000, 2000)));
+        let cancelled = renderer.render(Viewbox::initial(2000, 2000));
+        cancelled.cancel();
+        let next = renderer.render(position);
+        assert!(matches!(block_on(cancelled), Err(Cancelled)));
+        assert!(block_on(next).is_ok());
+    }
+
+    #[test]
+    fn test_async_render_shutdown() {
+        let mut renderer = AsyncRenderer::<defaults::State>::spawn(|| mandelbrot(60, 40));
+        let pending: Vec<_> = (0..4)
+            .map(|_| renderer.render(Viewbox::initial(4000, 4000)))
+            .collect();
+        let start = std::time::Instant::now();
+        renderer.shutdown();
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        for render in pending {
+            assert!(matches!(block_on(render), Err(Cancelled)));
+        }
+        assert!(matches!(
+            block_on(renderer.render(Viewbox::initial(60, 40))),
+            Err(Cancelled)
+        ));
+    }
+
+    #[test]
+    fn test_oneshot() {
+        let (tx, rx) = oneshot();
+        let handle = thread::spawn(move || {
+            thread::sleep(std::time::Duration::from_millis(20));
+            tx.send(42);
+        });
+        assert_eq!(block_on(rx), Ok(42));
+        handle.join().unwrap();
+
+        let (tx, rx) = oneshot::<i32>();
+        assert!(!tx.is_closed());
+        drop(tx);
+        assert_eq!(block_on(rx), Err(Cancelled));
+
+        let (tx, rx) = oneshot::<i32>();
+        drop(rx);
+        assert!(tx.is_closed());
+    }
+}
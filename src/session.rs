@@ -0,0 +1,380 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::slice;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+
+use crate::coord::Viewbox;
+use crate::{
+    apply_view_action, export, view, Frame, MAction, MandelbrotWorker, Quality, RenderSettings,
+    View, MAX_ITERATIONS,
+};
+
+/// An action sent to a worker, with its time from the start of the recording.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionEvent {
+    pub at: Duration,
+    pub action: MAction,
+}
+
+/// Writes the actions sent to a worker as a session, one JSON event per line.
+///
+/// Each line is flushed as it is written, so a session cut short, e.g. by the
+/// crash it is meant to reproduce, is readable up to its last action.
+pub struct SessionRecorder {
+    writer: Box<dyn Write + Send>,
+    started: Instant,
+    error: Option<io::Error>,
+}
+
+impl SessionRecorder {
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            writer: Box::new(writer),
+            started: Instant::now(),
+            error: None,
+        }
+    }
+
+    /// Record to a new file at `path`, replacing any file there.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Record `action`. Once a write failed, nothing more is recorded, and
+    /// the error is returned by `finish`.
+    pub fn record(&mut self, action: MAction) {
+        if self.error.is_some() {
+            return;
+        }
+        let event = SessionEvent {
+            at: self.started.elapsed(),
+            action,
+        };
+        let written = serde_json::to_writer(&mut self.writer, &event)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(self.writer))
+            .and_then(|()| self.writer.flush());
+        self.error = written.err();
+    }
+
+    /// Stop recording, with the first error writing the session, if any.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+/// How fast `Session::replay` sends actions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReplaySpeed {
+    /// As far apart as they were recorded.
+    RealTime,
+    /// One after the other, so the worker batches them and only renders
+    /// where they lead.
+    AsFastAsPossible,
+}
+
+/// A recorded session, to replay on a worker or render frame by frame.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Session {
+    pub events: Vec<SessionEvent>,
+}
+
+impl Session {
+    /// Read a session as written by a `SessionRecorder`.
+    pub fn read<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut events = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                events.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(Self { events })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for event in &self.events {
+            serde_json::to_writer(&mut writer, event)?;
+            writeln!(writer)?;
+        }
+        writer.flush()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+
+    /// Time from the start of the recording to the last action.
+    pub fn duration(&self) -> Duration {
+        self.events.last().map_or(Duration::ZERO, |event| event.at)
+    }
+
+    /// Send the actions to `worker`, returning once the last one is sent.
+    /// Invalid actions, e.g. from a session edited by hand, are skipped.
+    pub fn replay(&self, worker: &MandelbrotWorker, speed: ReplaySpeed) {
+        let started = Instant::now();
+        for event in &self.events {
+            if speed == ReplaySpeed::RealTime {
+                if let Some(wait) = event.at.checked_sub(started.elapsed()) {
+                    thread::sleep(wait);
+                }
+            }
+            let _ = worker.send(event.action);
+        }
+    }
+
+    /// Render the session one action at a time, starting with `settings`.
+    ///
+    /// Unlike a replay on a worker, no action is batched or cancelled, so a
+    /// session always gives the same frames, e.g. for an animation or a test.
+    /// Invalid actions are skipped, as in a replay.
+    ///
+    /// Panics if `settings.iterations` is over `MAX_ITERATIONS`.
+    pub fn frames(&self, settings: RenderSettings) -> SessionFrames<'_> {
+        assert!(
+            settings.iterations <= MAX_ITERATIONS,
+            "iterations must be at most i16::MAX"
+        );
+        SessionFrames {
+            events: self.events.iter(),
+            settings,
+            view: None,
+            sequence: 0,
+        }
+    }
+}
+
+/// Frames of a session: one per action that changes the image, and one per
+/// export. See `Session::frames`.
+pub struct SessionFrames<'a> {
+    events: slice::Iter<'a, SessionEvent>,
+    settings: RenderSettings,
+    view: Option<Box<dyn View>>,
+    sequence: u64,
+}
+
+impl SessionFrames<'_> {
+    fn frame(
+        &mut self,
+        image: RgbImage,
+        position: Viewbox,
+        quality: Quality,
+        started: Instant,
+    ) -> Frame {
+        let frame = Frame {
+            image,
+            position,
+            sequence: self.sequence,
+            duration: started.elapsed(),
+            settings: self.settings,
+            quality,
+        };
+        self.sequence += 1;
+        frame
+    }
+
+    /// Solve the view again, after a change of settings.
+    fn rebuild(&mut self) {
+        if let Some(position) = self.view.as_ref().map(|v| v.position()) {
+            self.view = Some(view(&self.settings, position));
+        }
+    }
+
+    fn apply(&mut self, action: MAction) -> Option<Frame> {
+        let started = Instant::now();
+        match action {
+            MAction::SetPalette(palette) => self.settings.palette = palette,
+            MAction::SetIterations(iterations) => {
+                self.settings.iterations = iterations;
+                self.rebuild();
+            }
+            MAction::SetSolver(solver) => {
+                self.settings.solver = solver;
+                self.rebuild();
+            }
+            MAction::SetFormula(formula) => {
+                self.settings.formula = formula;
+                self.rebuild();
+            }
+            MAction::RequestHighResExport(scale) => {
+                let position = self.view.as_ref()?.position().upscaled(scale);
+                let image = export(&self.settings, position);
+                return Some(self.frame(image, position, Quality::Export, started));
+            }
+            _ => {
+                let mut position = self.view.as_ref().map(|v| v.position());
+                apply_view_action(&mut position, action);
+                match (&mut self.view, position) {
+                    (Some(current), Some(position)) => current.set_position_fast(position),
+                    (None, Some(position)) => self.view = Some(view(&self.settings, position)),
+                    (_, None) => return None,
+                }
+            }
+        }
+        let view = self.view.as_ref()?;
+        let image = view.paint(self.settings.palette, self.settings.max_i_value());
+        let position = view.position();
+        Some(self.frame(image, position, Quality::Final, started))
+    }
+}
+
+impl Iterator for SessionFrames<'_> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        while let Some(event) = self.events.next().copied() {
+            let position = self.view.as_ref().map(|v| v.position());
+            if event.action.validate_at(position).is_err() {
+                continue;
+            }
+            if let Some(frame) = self.apply(event.action) {
+                return Some(frame);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::painter::Palette;
+    use crate::solver::{VecSolver, VecState};
+    use crate::{mandelbrot, Mandelbrot, MAX_EXPORT_SCALE};
+
+    fn session(actions: &[(u64, MAction)]) -> Session {
+        Session {
+            events: actions
+                .iter()
+                .map(|&(ms, action)| SessionEvent {
+                    at: Duration::from_millis(ms),
+                    action,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_session_files() {
+        let path =
+            std::env::temp_dir().join(format!("mandelox-session-{}.jsonl", std::process::id()));
+        let mut recorder = SessionRecorder::create(&path).unwrap();
+        recorder.record(MAction::Reset(40, 30));
+        recorder.record(MAction::Pan(3, -2));
+        recorder.finish().unwrap();
+        let recorded = Session::load(&path).unwrap();
+        let actions: Vec<MAction> = recorded.events.iter().map(|e| e.action).collect();
+        assert_eq!(actions, vec![MAction::Reset(40, 30), MAction::Pan(3, -2)]);
+        assert!(recorded.events[0].at <= recorded.events[1].at);
+
+        let saved = session(&[(0, MAction::Reset(40, 30)), (150, MAction::Zoom(2.0))]);
+        saved.save(&path).unwrap();
+        let loaded = Session::load(&path).unwrap();
+        assert_eq!(loaded, saved);
+        assert_eq!(loaded.duration(), Duration::from_millis(150));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    fn wait_for_frame(worker: &MandelbrotWorker, expected: &RgbImage) {
+        for _ in 0..400 {
+            if worker.next_image().as_ref() == Some(expected) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(25));
+        }
+        panic!("worker did not paint the expected image");
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let path =
+            std::env::temp_dir().join(format!("mandelox-replay-{}.jsonl", std::process::id()));
+        let worker = MandelbrotWorker::new();
+        worker.reset(40, 30);
+        worker.set_iterations(50);
+        // Recorded as the starting position and settings.
+        worker.record(SessionRecorder::create(&path).unwrap());
+        worker.pan(5, 3);
+        worker.zoom(2.0);
+        worker.set_palette(Palette::Greyscale);
+        worker.stop_recording().unwrap().unwrap();
+        // Not recorded.
+        worker.pan(20, 20);
+
+        let mut m = Mandelbrot::<VecState>::with_solver(VecSolver::new(2.0, 50), 40, 30);
+        m.pan(5, 3);
+        m.zoom(2.0);
+        let expected = m.paint(Palette::Greyscale, 50);
+
+        let recorded = Session::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(recorded.events.len(), 8);
+        assert_eq!(
+            recorded.events[0].action,
+            MAction::SetViewbox(Viewbox::initial(40, 30))
+        );
+        for speed in [ReplaySpeed::AsFastAsPossible, ReplaySpeed::RealTime] {
+            let replayed = MandelbrotWorker::new();
+            recorded.replay(&replayed, speed);
+            wait_for_frame(&replayed, &expected);
+        }
+
+        let started = Instant::now();
+        session(&[(0, MAction::Reset(4, 3)), (150, MAction::Pan(1, 0))])
+            .replay(&worker, ReplaySpeed::RealTime);
+        assert!(started.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[test]
+    fn test_session_frames() {
+        let session = session(&[
+            (0, MAction::Pan(1, 1)),
+            (10, MAction::Reset(40, 30)),
+            (20, MAction::Pan(3, 2)),
+            (30, MAction::SetPalette(Palette::Greyscale)),
+            (40, MAction::RequestHighResExport(2)),
+            (50, MAction::Zoom(2.0)),
+        ]);
+        let frames: Vec<Frame> = session.frames(RenderSettings::default()).collect();
+        // The pan before the reset has no view to move.
+        let qualities: Vec<Quality> = frames.iter().map(|f| f.quality).collect();
+        use Quality::*;
+        assert_eq!(qualities, vec![Final, Final, Final, Export, Final]);
+        let sequences: Vec<u64> = frames.iter().map(|f| f.sequence).collect();
+        assert_eq!(sequences, vec![0, 1, 2, 3, 4]);
+
+        let mut m = mandelbrot(40, 30);
+        m.pan(3, 2);
+        assert_eq!(frames[1].image, m.paint(Palette::Rainbow, 100));
+        assert_eq!(frames[2].image, m.paint(Palette::Greyscale, 100));
+        assert_eq!(frames[3].image.dimensions(), (80, 60));
+        m.zoom(2.0);
+        assert_eq!(frames[4].position, m.position);
+        assert_eq!(frames[4].image, m.paint(Palette::Greyscale, 100));
+    }
+
+    #[test]
+    fn test_session_frames_skip_invalid() {
+        // E.g. an export too large for the frame.
+        let invalid = session(&[
+            (0, MAction::Reset(1100, 1000)),
+            (10, MAction::RequestHighResExport(MAX_EXPORT_SCALE)),
+            (20, MAction::Zoom(0.0)),
+        ]);
+        let frames: Vec<Frame> = invalid.frames(RenderSettings::default()).collect();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].quality, Quality::Final);
+    }
+}
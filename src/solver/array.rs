@@ -1,11 +1,14 @@
 use std::sync::Arc;
 
-use ndarray::{concatenate, s, Array, Array1, Array2, Axis, Zip};
+use ndarray::{
+    concatenate, s, Array, Array1, Array2, ArrayView2, Axis, Ix2, Slice, SliceInfo, SliceInfoElem,
+    Zip,
+};
 
 use crate::complex::*;
-use crate::coord::Coords;
-use crate::solver::{MbState, Solver};
-use crate::threads::{Join, RangeSplitter, Split};
+use crate::coord::{Coords, Point, Viewbox};
+use crate::solver::{Formula, MbState, Solver};
+use crate::threads::{CancelToken, Cancelled, Join, RangeSplitter, Split};
 use crate::D2ArrayLike;
 
 #[derive(Clone, Debug)]
@@ -41,6 +44,25 @@ impl From<Coords<C<f64>>> for ArrayState {
     }
 }
 
+impl From<Viewbox> for ArrayState {
+    fn from(v: Viewbox) -> Self {
+        let width = v.width as usize;
+        let height = v.height as usize;
+        let ca: Array2<C<f64>> =
+            Array2::from_shape_vec((height, width), v.coordinates().collect()).unwrap();
+        let za = ca.clone();
+        let ia: Array2<i16> = Array::from_elem((height, width), -1);
+        Self {
+            width,
+            height,
+            iteration: 0,
+            ca: Arc::new(ca),
+            za: Arc::new(za),
+            ia: Arc::new(ia),
+        }
+    }
+}
+
 impl MbState for ArrayState {
     fn width(&self) -> usize {
         self.width
@@ -110,10 +132,196 @@ impl Join for ArrayState {
     }
 }
 
+/// Shift an array by `shift` along `axis`, filling the exposed band from `fill`,
+/// or leaving its previous contents when there is no fill.
+fn shifted<'a, A: Clone>(
+    a: &'a Array2<A>,
+    axis: Axis,
+    shift: i64,
+    fill: Option<ArrayView2<'a, A>>,
+) -> Array2<A> {
+    let len = a.len_of(axis);
+    let n = (shift.unsigned_abs() as usize).min(len);
+    // A shift past the whole array only keeps the part of the fill that
+    // lands in it, as `D2ArrayLike::shift_rows` does.
+    let fill = fill.map(|mut fill| {
+        let skip = if shift > 0 { 0 } else { fill.len_of(axis) - n };
+        fill.slice_axis_inplace(axis, Slice::from(skip..skip + n));
+        fill
+    });
+    let (head, tail) = if shift > 0 {
+        let head = fill.unwrap_or_else(|| a.slice_axis(axis, Slice::from(..n)));
+        (head, a.slice_axis(axis, Slice::from(..len - n)))
+    } else {
+        let tail = fill.unwrap_or_else(|| a.slice_axis(axis, Slice::from(len - n..)));
+        (a.slice_axis(axis, Slice::from(n..)), tail)
+    };
+    concatenate(axis, &[head, tail]).unwrap()
+}
+
+impl ArrayState {
+    fn shift(&mut self, axis: Axis, shift: i64, copy_from: Option<&Self>) {
+        if shift == 0 {
+            return;
+        }
+        self.ca = Arc::new(shifted(
+            &self.ca,
+            axis,
+            shift,
+            copy_from.map(|c| c.ca.view()),
+        ));
+        self.za = Arc::new(shifted(
+            &self.za,
+            axis,
+            shift,
+            copy_from.map(|c| c.za.view()),
+        ));
+        self.ia = Arc::new(shifted(
+            &self.ia,
+            axis,
+            shift,
+            copy_from.map(|c| c.ia.view()),
+        ));
+    }
+}
+
+impl D2ArrayLike for ArrayState {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            iteration: 0,
+            ca: Arc::new(Array2::zeros((height, width))),
+            za: Arc::new(Array2::zeros((height, width))),
+            ia: Arc::new(Array2::zeros((height, width))),
+        }
+    }
+    fn width(&self) -> usize {
+        self.width
+    }
+    fn height(&self) -> usize {
+        self.height
+    }
+    fn copy_from(&mut self, other: &Self, from: Point<usize>, to: Point<usize>) {
+        let (from, to) = ([from.y, from.x], [to.y, to.x]);
+        Arc::make_mut(&mut self.ca)[to] = other.ca[from];
+        Arc::make_mut(&mut self.za)[to] = other.za[from];
+        Arc::make_mut(&mut self.ia)[to] = other.ia[from];
+        self.iteration = other.iteration;
+    }
+    fn copy_self(&mut self, from: Point<usize>, to: Point<usize>) {
+        let (from, to) = ([from.y, from.x], [to.y, to.x]);
+        let ca = Arc::make_mut(&mut self.ca);
+        ca[to] = ca[from];
+        let za = Arc::make_mut(&mut self.za);
+        za[to] = za[from];
+        let ia = Arc::make_mut(&mut self.ia);
+        ia[to] = ia[from];
+    }
+    fn copy_span_from(&mut self, other: &Self, from: Point<usize>, to: Point<usize>, len: usize) {
+        self.copy_rect_from(other, from, to, len, 1);
+    }
+    fn copy_span_self(&mut self, from: Point<usize>, to: Point<usize>, len: usize) {
+        self.copy_rect_self(from, to, len, 1);
+    }
+    fn copy_rect_from(
+        &mut self,
+        other: &Self,
+        from: Point<usize>,
+        to: Point<usize>,
+        width: usize,
+        height: usize,
+    ) {
+        let src = s![from.y..from.y + height, from.x..from.x + width];
+        let dst = s![to.y..to.y + height, to.x..to.x + width];
+        Arc::make_mut(&mut self.ca)
+            .slice_mut(dst)
+            .assign(&other.ca.slice(src));
+        Arc::make_mut(&mut self.za)
+            .slice_mut(dst)
+            .assign(&other.za.slice(src));
+        Arc::make_mut(&mut self.ia)
+            .slice_mut(dst)
+            .assign(&other.ia.slice(src));
+        self.iteration = other.iteration;
+    }
+    fn copy_rect_self(
+        &mut self,
+        from: Point<usize>,
+        to: Point<usize>,
+        width: usize,
+        height: usize,
+    ) {
+        // The regions may overlap, so the source is copied out first.
+        type Rect = SliceInfo<[SliceInfoElem; 2], Ix2, Ix2>;
+        fn copy<A: Clone>(a: &mut Array2<A>, src: Rect, dst: Rect) {
+            let block = a.slice(src).to_owned();
+            a.slice_mut(dst).assign(&block);
+        }
+        let src = s![from.y..from.y + height, from.x..from.x + width];
+        let dst = s![to.y..to.y + height, to.x..to.x + width];
+        copy(Arc::make_mut(&mut self.ca), src, dst);
+        copy(Arc::make_mut(&mut self.za), src, dst);
+        copy(Arc::make_mut(&mut self.ia), src, dst);
+    }
+    fn copy_slice(&self, p1: Point<usize>, p2: Point<usize>) -> Self {
+        let (xmin, xmax) = (p1.x.min(p2.x), p1.x.max(p2.x));
+        let (ymin, ymax) = (p1.y.min(p2.y), p1.y.max(p2.y));
+        assert!(xmax <= self.width);
+        assert!(ymax <= self.height);
+        let slice = s![ymin..ymax, xmin..xmax];
+        Self {
+            width: xmax - xmin,
+            height: ymax - ymin,
+            iteration: self.iteration,
+            ca: Arc::new(self.ca.slice(slice).into_owned()),
+            za: Arc::new(self.za.slice(slice).into_owned()),
+            ia: Arc::new(self.ia.slice(slice).into_owned()),
+        }
+    }
+    fn merge_rows(a: &Self, b: &Self) -> Self {
+        assert!(b.width == a.width, "different width");
+        Self {
+            width: a.width,
+            height: a.height + b.height,
+            iteration: a.iteration,
+            ca: Arc::new(concatenate(Axis(0), &[a.ca.view(), b.ca.view()]).unwrap()),
+            za: Arc::new(concatenate(Axis(0), &[a.za.view(), b.za.view()]).unwrap()),
+            ia: Arc::new(concatenate(Axis(0), &[a.ia.view(), b.ia.view()]).unwrap()),
+        }
+    }
+    fn merge_cols(a: &Self, b: &Self) -> Self {
+        assert!(b.height == a.height, "different height");
+        Self {
+            width: a.width + b.width,
+            height: a.height,
+            iteration: a.iteration,
+            ca: Arc::new(concatenate(Axis(1), &[a.ca.view(), b.ca.view()]).unwrap()),
+            za: Arc::new(concatenate(Axis(1), &[a.za.view(), b.za.view()]).unwrap()),
+            ia: Arc::new(concatenate(Axis(1), &[a.ia.view(), b.ia.view()]).unwrap()),
+        }
+    }
+    fn shift_rows(&mut self, row: i64, copy_from: Option<&Self>) {
+        if let Some(copysrc) = copy_from {
+            assert!(copysrc.height == row.unsigned_abs() as usize);
+            assert!(copysrc.width == self.width);
+        }
+        self.shift(Axis(0), row, copy_from);
+    }
+    fn shift_cols(&mut self, col: i64, copy_from: Option<&Self>) {
+        if let Some(copysrc) = copy_from {
+            assert!(copysrc.height == self.height);
+            assert!(copysrc.width == col.unsigned_abs() as usize);
+        }
+        self.shift(Axis(1), col, copy_from);
+    }
+}
+
 #[derive(Clone)]
 pub struct ArraySolver {
     iterations: u16,
     treshold: f64,
+    formula: Formula,
 }
 
 impl ArraySolver {
@@ -121,12 +329,19 @@ impl ArraySolver {
         Self {
             treshold,
             iterations,
+            formula: Formula::Mandelbrot,
         }
     }
 
+    pub fn with_formula(mut self, formula: Formula) -> Self {
+        self.formula = formula;
+        self
+    }
+
     fn iterate(&self, state: &ArrayState) -> ArrayState {
         let mut new_za = Array2::zeros((state.height, state.width));
         let mut new_ia = Array2::zeros((state.height, state.width));
+        let parameter = self.formula.parameter();
 
         Zip::from(state.ia.as_ref())
             .and(&mut new_ia)
@@ -134,7 +349,7 @@ impl ArraySolver {
             .and(&mut new_za)
             .and(state.ca.as_ref())
             .for_each(|&iv, niv, &zv, nzv, &cv| {
-                *nzv = (zv * zv) + cv;
+                *nzv = (zv * zv) + parameter.unwrap_or(cv);
                 *niv = if (iv == -1) && (nzv.norm() > self.treshold) {
                     state.iteration + 1
                 } else {
@@ -143,8 +358,8 @@ impl ArraySolver {
             });
 
         ArrayState {
-            height: state.height(),
-            width: state.width(),
+            height: state.height,
+            width: state.width,
             iteration: state.iteration + 1,
             ca: state.ca.clone(),
             za: Arc::new(new_za),
@@ -160,10 +375,19 @@ impl Default for ArraySolver {
 }
 
 impl Solver<ArrayState> for ArraySolver {
-    fn solve(&self, mut state: ArrayState) -> ArrayState {
+    fn solve(&self, state: ArrayState) -> ArrayState {
+        self.solve_cancellable(state, &CancelToken::new()).unwrap()
+    }
+
+    fn solve_cancellable(
+        &self,
+        mut state: ArrayState,
+        cancel: &CancelToken,
+    ) -> Result<ArrayState, Cancelled> {
         for _ in 0..self.iterations {
+            cancel.check()?;
             state = self.iterate(&state);
         }
-        state
+        Ok(state)
     }
 }
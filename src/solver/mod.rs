@@ -1,43 +1,192 @@
 use std::cmp::Ordering;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
 
 use crate::complex::C;
-use crate::coord::{Coords, Point};
-use crate::threads::{Call, Join, Split, WorkerPool};
+use crate::coord::{Coords, Point, Viewbox};
+use crate::threads::{
+    Call, CancelToken, Cancelled, Join, JoinTiles, Progress, RangeSplitter, Split, SplitTiles,
+    Tile, WorkerPool,
+};
 
 pub mod array;
+#[cfg(feature = "rayon")]
+pub mod rayonpool;
+pub mod sarray;
+pub mod sharedvec;
 pub mod simdvec;
 pub mod vec;
 
 pub use array::{ArraySolver, ArrayState};
+#[cfg(feature = "rayon")]
+pub use rayonpool::RayonSolver;
+pub use sharedvec::SharedVecState;
 pub use simdvec::{SimdVecSolver, SimdVecState};
 pub use vec::{VecSolver, VecState};
 
 pub trait Solver<T> {
     fn solve(&self, state: T) -> T;
 
-    fn threaded(self, n: usize) -> WorkerPool<T, T>
+    /// Solve a state that may not have been built yet.
+    fn solve_lazy(&self, state: Lazy<T>) -> T
+    where
+        T: MbState + Join,
+    {
+        self.solve(state.force())
+    }
+
+    /// Like `solve`, but give up early once `cancel` is cancelled.
+    ///
+    /// The token is only checked before starting, unless the solver checks it
+    /// between iterations.
+    fn solve_cancellable(&self, state: T, cancel: &CancelToken) -> Result<T, Cancelled> {
+        cancel.check()?;
+        Ok(self.solve(state))
+    }
+
+    fn solve_lazy_cancellable(&self, state: Lazy<T>, cancel: &CancelToken) -> Result<T, Cancelled>
+    where
+        T: MbState + Join,
+    {
+        cancel.check()?;
+        self.solve_cancellable(state.force(), cancel)
+    }
+
+    /// Progress of the current or last solve, for solvers that report it.
+    fn progress(&self) -> Option<Progress> {
+        None
+    }
+
+    fn threaded(self, n: usize) -> WorkerPool<Lazy<T>, T>
     where
         Self: Clone + Send + 'static,
-        T: Split + Join + Send + 'static,
+        T: MbState + Split + Join + Send + 'static,
     {
-        WorkerPool::with(n, || {
-            let solver = self.clone();
-            move |state| solver.solve(state)
-        })
+        WorkerPool::with(n, || SolverCall(self.clone()))
     }
 }
 
-impl<T> Solver<T> for WorkerPool<T, T>
+/// A solver, as the function run by the workers of a pool.
+struct SolverCall<S>(S);
+
+impl<S, T> Call<Lazy<T>, T> for SolverCall<S>
 where
-    Self: Call<T, T>,
+    S: Solver<T>,
+    T: MbState + Join,
+{
+    fn call(&self, state: Lazy<T>) -> T {
+        self.0.solve(state.force())
+    }
+
+    fn call_cancellable(&self, state: Lazy<T>, cancel: &CancelToken) -> Result<T, Cancelled> {
+        self.0.solve_lazy_cancellable(state, cancel)
+    }
+}
+
+impl<T> Solver<T> for WorkerPool<Lazy<T>, T>
+where
+    Self: Call<Lazy<T>, T>,
     T: MbState + Split + Join,
 {
     fn solve(&self, state: T) -> T {
+        self.call(Lazy::Ready(state))
+    }
+
+    /// Pending states are split before they are built, so each worker computes
+    /// the coordinates of its own part.
+    fn solve_lazy(&self, state: Lazy<T>) -> T {
         self.call(state)
     }
+
+    fn solve_cancellable(&self, state: T, cancel: &CancelToken) -> Result<T, Cancelled> {
+        self.call_cancellable(Lazy::Ready(state), cancel)
+    }
+
+    fn solve_lazy_cancellable(&self, state: Lazy<T>, cancel: &CancelToken) -> Result<T, Cancelled> {
+        self.call_cancellable(state, cancel)
+    }
+
+    fn progress(&self) -> Option<Progress> {
+        Some(WorkerPool::progress(self))
+    }
+}
+
+/// A state, or the viewboxes to build it from.
+///
+/// A pending state is a vertical stack of viewboxes of the same width; it is
+/// only built when forced, so splitting it just splits the viewboxes.
+#[derive(Clone, Debug)]
+pub enum Lazy<T> {
+    Pending(Vec<Viewbox>),
+    Ready(T),
+}
+
+impl<T> Lazy<T> {
+    pub fn viewbox(viewbox: Viewbox) -> Self {
+        Self::Pending(vec![viewbox])
+    }
+}
+
+impl<T> Lazy<T>
+where
+    T: MbState + Join,
+{
+    pub fn force(self) -> T {
+        match self {
+            Self::Ready(state) => state,
+            Self::Pending(mut stack) if stack.len() == 1 => stack.pop().unwrap().into(),
+            Self::Pending(stack) => T::join_vec(stack.into_iter().map(T::from).collect()),
+        }
+    }
+}
+
+/// Rows `start..end` of a stack of viewboxes, as a stack of sub-viewboxes.
+fn stack_rows(stack: &[Viewbox], start: i64, end: i64) -> Vec<Viewbox> {
+    let mut rows = vec![];
+    let mut top = 0;
+    for viewbox in stack {
+        let bottom = top + viewbox.height;
+        let (a, b) = (start.max(top), end.min(bottom));
+        if a < b {
+            rows.push(viewbox.region(0, a - top, viewbox.width, b - a));
+        }
+        top = bottom;
+    }
+    if rows.is_empty() {
+        rows.push(stack[0].region(0, 0, stack[0].width, 0));
+    }
+    rows
 }
 
-pub trait MbState: From<Coords<C<f64>>> {
+impl<T> Split for Lazy<T>
+where
+    T: MbState + Split + Join,
+{
+    fn split_to_vec(self, n: usize) -> Vec<Self> {
+        match self {
+            Self::Pending(stack) if !T::BUILD_BEFORE_SPLIT => {
+                let height: i64 = stack.iter().map(|v| v.height).sum();
+                RangeSplitter::split(0, height as usize, n)
+                    .map(|(start, end)| Self::Pending(stack_rows(&stack, start as i64, end as i64)))
+                    .collect()
+            }
+            state => state
+                .force()
+                .split_to_vec(n)
+                .into_iter()
+                .map(Self::Ready)
+                .collect(),
+        }
+    }
+}
+
+pub trait MbState: From<Coords<C<f64>>> + From<Viewbox> {
+    /// Whether a pending state is built whole before it is split, instead of
+    /// each part being built from its own viewboxes: parts of a state built
+    /// whole may join back without a copy.
+    const BUILD_BEFORE_SPLIT: bool = false;
+
     fn width(&self) -> usize;
     fn height(&self) -> usize;
     fn i_value(&self, x: usize, y: usize) -> i16;
@@ -49,6 +198,80 @@ pub trait D2ArrayLike: Sized {
     fn height(&self) -> usize;
     fn copy_from(&mut self, other: &Self, from: Point<usize>, to: Point<usize>);
     fn copy_self(&mut self, from: Point<usize>, to: Point<usize>);
+    /// Copy `len` pixels of a row from `other`, starting at `from`, to `to`.
+    fn copy_span_from(&mut self, other: &Self, from: Point<usize>, to: Point<usize>, len: usize) {
+        for dx in 0..len {
+            self.copy_from(
+                other,
+                Point::new(from.x + dx, from.y),
+                Point::new(to.x + dx, to.y),
+            );
+        }
+    }
+    /// Copy `len` pixels of a row within the array; the span may overlap itself.
+    fn copy_span_self(&mut self, from: Point<usize>, to: Point<usize>, len: usize) {
+        let copy = |s: &mut Self, dx: usize| {
+            s.copy_self(Point::new(from.x + dx, from.y), Point::new(to.x + dx, to.y))
+        };
+        if from.y == to.y && to.x > from.x {
+            (0..len).rev().for_each(|dx| copy(self, dx));
+        } else {
+            (0..len).for_each(|dx| copy(self, dx));
+        }
+    }
+    /// Whether rows are stored one after the other, so that a span running
+    /// past the end of a row goes on with the next one, and rectangles of
+    /// whole rows are copied as a single span.
+    fn row_contiguous(&self) -> bool {
+        false
+    }
+    /// Copy a `width` x `height` rectangle from `other`, with its top-left
+    /// corner at `from`, to `to`.
+    fn copy_rect_from(
+        &mut self,
+        other: &Self,
+        from: Point<usize>,
+        to: Point<usize>,
+        width: usize,
+        height: usize,
+    ) {
+        if self.row_contiguous() && width == self.width() && width == other.width() {
+            return self.copy_span_from(other, from, to, width * height);
+        }
+        for dy in 0..height {
+            self.copy_span_from(
+                other,
+                Point::new(from.x, from.y + dy),
+                Point::new(to.x, to.y + dy),
+                width,
+            );
+        }
+    }
+    /// Copy a `width` x `height` rectangle within the array; the source and
+    /// destination may overlap.
+    fn copy_rect_self(
+        &mut self,
+        from: Point<usize>,
+        to: Point<usize>,
+        width: usize,
+        height: usize,
+    ) {
+        if self.row_contiguous() && width == self.width() {
+            return self.copy_span_self(from, to, width * height);
+        }
+        let copy = |s: &mut Self, dy: usize| {
+            s.copy_span_self(
+                Point::new(from.x, from.y + dy),
+                Point::new(to.x, to.y + dy),
+                width,
+            )
+        };
+        if to.y > from.y {
+            (0..height).rev().for_each(|dy| copy(self, dy));
+        } else {
+            (0..height).for_each(|dy| copy(self, dy));
+        }
+    }
     fn copy_slice(&self, p1: Point<usize>, p2: Point<usize>) -> Self {
         let xmax = p1.x.max(p2.x);
         assert!(xmax <= self.width());
@@ -56,13 +279,15 @@ pub trait D2ArrayLike: Sized {
         let ymax = p1.y.max(p2.y);
         assert!(ymax <= self.height());
         let ymin = p1.y.min(p2.y);
-        let mut new = Self::new(xmax - xmin, ymax - ymin);
-        for y in ymin..ymax {
-            for x in xmin..xmax {
-                new.copy_from(self, Point::new(x, y), Point::new(x - xmin, y - ymin));
-            }
-        }
-
+        let (width, height) = (xmax - xmin, ymax - ymin);
+        let mut new = Self::new(width, height);
+        new.copy_rect_from(
+            self,
+            Point::new(xmin, ymin),
+            Point::new(0, 0),
+            width,
+            height,
+        );
         new
     }
     fn idx(&self, row: i64, col: i64) -> (usize, usize, usize, usize) {
@@ -110,16 +335,8 @@ pub trait D2ArrayLike: Sized {
         let a_h = a.height();
         let b_h = b.height();
         let mut new = Self::new(width, a_h + b_h);
-        for y in 0..a_h {
-            for x in 0..width {
-                new.copy_from(a, Point::new(x, y), Point::new(x, y));
-            }
-        }
-        for y in a_h..a_h + b_h {
-            for x in 0..width {
-                new.copy_from(b, Point::new(x, y - a_h), Point::new(x, y))
-            }
-        }
+        new.copy_rect_from(a, Point::new(0, 0), Point::new(0, 0), width, a_h);
+        new.copy_rect_from(b, Point::new(0, 0), Point::new(0, a_h), width, b_h);
         new
     }
     fn merge_cols(a: &Self, b: &Self) -> Self {
@@ -128,16 +345,8 @@ pub trait D2ArrayLike: Sized {
         let a_w = a.width();
         let b_w = b.width();
         let mut new = Self::new(a_w + b_w, height);
-        for y in 0..height {
-            for x in 0..a_w {
-                new.copy_from(a, Point::new(x, y), Point::new(x, y));
-            }
-        }
-        for y in 0..height {
-            for x in a_w..a_w + b_w {
-                new.copy_from(b, Point::new(x - a_w, y), Point::new(x, y))
-            }
-        }
+        new.copy_rect_from(a, Point::new(0, 0), Point::new(0, 0), a_w, height);
+        new.copy_rect_from(b, Point::new(0, 0), Point::new(a_w, 0), b_w, height);
         new
     }
     fn shift_rows(&mut self, row: i64, copy_from: Option<&Self>) {
@@ -145,26 +354,16 @@ pub trait D2ArrayLike: Sized {
             assert!(copysrc.height() == row.unsigned_abs() as usize);
             assert!(copysrc.width() == self.width());
         }
-        let h = self.height() as i64;
-        let (y_iter, cp_y_offset): (Box<dyn Iterator<Item = usize>>, i64) = match row.cmp(&0) {
-            Ordering::Greater => (Box::new((0..self.height()).rev()), row),
-            Ordering::Less => (Box::new(0..self.height()), -h),
+        let (w, h) = (self.width(), self.height());
+        let n = (row.unsigned_abs() as usize).min(h);
+        let (kept_from, kept_to, exposed, copied) = match row.cmp(&0) {
+            Ordering::Greater => (0, n, 0, 0),
+            Ordering::Less => (n, 0, h - n, row.unsigned_abs() as usize - n),
             Ordering::Equal => return,
         };
-
-        for y in y_iter {
-            let yi = y as i64;
-            let source_y = yi - row;
-            for x in 0..self.width() {
-                let p = Point::new(x, y);
-                let delta_p = Point::new(x, source_y as usize);
-                if source_y >= 0 && source_y < h {
-                    self.copy_self(delta_p, p);
-                } else if let Some(copy_from) = copy_from {
-                    let cp_from_p = Point::new(x, (y as i64 - row + cp_y_offset) as usize);
-                    self.copy_from(copy_from, cp_from_p, p);
-                }
-            }
+        self.copy_rect_self(Point::new(0, kept_from), Point::new(0, kept_to), w, h - n);
+        if let Some(copysrc) = copy_from {
+            self.copy_rect_from(copysrc, Point::new(0, copied), Point::new(0, exposed), w, n);
         }
     }
     fn shift_cols(&mut self, col: i64, copy_from: Option<&Self>) {
@@ -172,29 +371,392 @@ pub trait D2ArrayLike: Sized {
             assert!(copysrc.height() == self.height());
             assert!(copysrc.width() == col.unsigned_abs() as usize);
         }
-        let w = self.width() as i64;
-        let (x_iter, cp_x_offset): (Box<dyn Iterator<Item = usize>>, i64) = match col.cmp(&0) {
-            Ordering::Greater => (Box::new((0..self.width()).rev()), col),
-            Ordering::Less => (Box::new(0..self.width()), -w),
+        let (w, h) = (self.width(), self.height());
+        let n = (col.unsigned_abs() as usize).min(w);
+        let (kept_from, kept_to, exposed, copied) = match col.cmp(&0) {
+            Ordering::Greater => (0, n, 0, 0),
+            Ordering::Less => (n, 0, w - n, col.unsigned_abs() as usize - n),
             Ordering::Equal => return,
         };
-        for x in x_iter {
-            let xi = x as i64;
-            let source_x = xi - col;
-            for y in 0..self.height() {
-                let p = Point::new(x, y);
-                let delta_p = Point::new(source_x as usize, y);
-                if source_x >= 0 && source_x < w {
-                    self.copy_self(delta_p, p);
-                } else if let Some(copy_from) = copy_from {
-                    let cp_from_p = Point::new((x as i64 - col + cp_x_offset) as usize, y);
-                    self.copy_from(copy_from, cp_from_p, p);
-                }
+        self.copy_rect_self(Point::new(kept_from, 0), Point::new(kept_to, 0), w - n, h);
+        if let Some(copysrc) = copy_from {
+            self.copy_rect_from(copysrc, Point::new(copied, 0), Point::new(exposed, 0), n, h);
+        }
+    }
+}
+
+impl<T> SplitTiles for T
+where
+    T: D2ArrayLike,
+{
+    fn split_tiles(self, width: usize, height: usize) -> Vec<Tile<Self>> {
+        assert!(width > 0 && height > 0, "empty tiles");
+        let mut tiles = vec![];
+        for y in (0..self.height()).step_by(height) {
+            for x in (0..self.width()).step_by(width) {
+                let end = Point::new(
+                    (x + width).min(self.width()),
+                    (y + height).min(self.height()),
+                );
+                let part = self.copy_slice(Point::new(x, y), end);
+                tiles.push(Tile::new(Point::new(x, y).as_i64(), part));
+            }
+        }
+        tiles
+    }
+}
+
+impl<T> JoinTiles for T
+where
+    T: D2ArrayLike,
+{
+    fn join_tiles<'a, I>(width: usize, height: usize, tiles: I) -> Self
+    where
+        Self: 'a,
+        I: IntoIterator<Item = Tile<&'a Self>>,
+    {
+        let mut whole = Self::new(width, height);
+        for Tile { origin, part } in tiles {
+            // Intersection of the tile with the whole, in the whole's pixels.
+            let x0 = origin.x.max(0);
+            let y0 = origin.y.max(0);
+            let x1 = (origin.x + part.width() as i64).min(width as i64);
+            let y1 = (origin.y + part.height() as i64).min(height as i64);
+            if x0 >= x1 || y0 >= y1 {
+                continue;
             }
+            whole.copy_rect_from(
+                part,
+                Point::new((x0 - origin.x) as usize, (y0 - origin.y) as usize),
+                Point::new(x0 as usize, y0 as usize),
+                (x1 - x0) as usize,
+                (y1 - y0) as usize,
+            );
         }
+        whole
     }
 }
 
-pub fn default_solver() -> WorkerPool<VecState, VecState> {
+/// A state stored as a single slice of cells, row after row, whose spans are
+/// copied as slices by `copy_cells_from` and `copy_cells_self`.
+pub(crate) trait RowMajor: D2ArrayLike {
+    type Cell: Clone;
+    fn cells(&self) -> &[Self::Cell];
+    fn cells_mut(&mut self) -> &mut [Self::Cell];
+}
+
+/// `D2ArrayLike::copy_span_from` of a `RowMajor` state.
+pub(crate) fn copy_cells_from<S: RowMajor>(
+    s: &mut S,
+    other: &S,
+    from: Point<usize>,
+    to: Point<usize>,
+    len: usize,
+) {
+    let (from, to) = (from.row_idx(other.width()), to.row_idx(s.width()));
+    s.cells_mut()[to..to + len].clone_from_slice(&other.cells()[from..from + len]);
+}
+
+/// `D2ArrayLike::copy_span_self` of a `RowMajor` state.
+pub(crate) fn copy_cells_self<S: RowMajor>(
+    s: &mut S,
+    from: Point<usize>,
+    to: Point<usize>,
+    len: usize,
+) {
+    let (from, to) = (from.row_idx(s.width()), to.row_idx(s.width()));
+    clone_within(s.cells_mut(), from..from + len, to);
+}
+
+/// `slice::copy_within` for types that are only `Clone`: clone the elements of
+/// `src` to `dest`, with memmove semantics when the ranges overlap.
+pub(crate) fn clone_within<T: Clone>(v: &mut [T], src: Range<usize>, dest: usize) {
+    let len = src.len();
+    if dest + len <= src.start {
+        let (head, tail) = v.split_at_mut(src.start);
+        head[dest..dest + len].clone_from_slice(&tail[..len]);
+    } else if src.end <= dest {
+        let (head, tail) = v.split_at_mut(dest);
+        tail[..len].clone_from_slice(&head[src]);
+    } else if dest < src.start {
+        (0..len).for_each(|n| v[dest + n] = v[src.start + n].clone());
+    } else {
+        (0..len)
+            .rev()
+            .for_each(|n| v[dest + n] = v[src.start + n].clone());
+    }
+}
+
+/// The iteration the solvers run, z -> z² + c: c is the pixel for the
+/// Mandelbrot set, or a fixed parameter for a Julia set.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Formula {
+    #[default]
+    Mandelbrot,
+    Julia {
+        re: f64,
+        im: f64,
+    },
+}
+
+impl Formula {
+    /// The c of the iteration, unless it is the pixel.
+    pub fn parameter(&self) -> Option<C<f64>> {
+        match *self {
+            Self::Mandelbrot => None,
+            Self::Julia { re, im } => Some(C::new(re, im)),
+        }
+    }
+}
+
+/// The solvers, with their states, that can be picked at runtime.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SolverKind {
+    #[default]
+    Vec,
+    SharedVec,
+    SimdVec,
+    Array,
+}
+
+pub fn default_solver() -> WorkerPool<Lazy<VecState>, VecState> {
     VecSolver::default().threaded(num_cpus::get_physical())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::threads::Join;
+
+    /// Iteration counts, copied with the per-pixel default implementations only.
+    struct Pixels(Coords<i16>);
+
+    impl Pixels {
+        fn of<T: MbState>(state: &T) -> Self {
+            let (width, height) = (state.width(), state.height());
+            let values = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| state.i_value(x, y))
+                .collect();
+            Self(Coords {
+                width,
+                height,
+                values,
+            })
+        }
+    }
+
+    impl D2ArrayLike for Pixels {
+        fn new(width: usize, height: usize) -> Self {
+            Self(Coords::new(width, height))
+        }
+        fn width(&self) -> usize {
+            self.0.width
+        }
+        fn height(&self) -> usize {
+            self.0.height
+        }
+        fn copy_from(&mut self, other: &Self, from: Point<usize>, to: Point<usize>) {
+            self.0.copy_from(&other.0, from, to);
+        }
+        fn copy_self(&mut self, from: Point<usize>, to: Point<usize>) {
+            self.0.copy_self(from, to);
+        }
+    }
+
+    fn check_block_copies<T, S>()
+    where
+        T: D2ArrayLike + MbState,
+        S: Solver<T> + Default,
+    {
+        let solver = S::default();
+        let mut state: T = solver.solve(Viewbox::initial(23, 11).into());
+        let other: T = solver.solve(Viewbox::initial(17, 9).with_rotation(0.5).into());
+        let mut expected = Pixels::of(&state);
+        let expected_other = Pixels::of(&other);
+
+        let mut seed: u64 = 7;
+        let mut next = move |m: usize| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % m
+        };
+        for n in 0..200 {
+            let (src_w, src_h) = if n % 2 == 0 { (17, 9) } else { (23, 11) };
+            let width = next(src_w + 1);
+            let height = next(src_h + 1);
+            let from = Point::new(next(src_w - width + 1), next(src_h - height + 1));
+            let to = Point::new(next(23 - width + 1), next(11 - height + 1));
+            if n % 2 == 0 {
+                state.copy_rect_from(&other, from, to, width, height);
+                expected.copy_rect_from(&expected_other, from, to, width, height);
+            } else {
+                state.copy_rect_self(from, to, width, height);
+                expected.copy_rect_self(from, to, width, height);
+            }
+            for y in 0..11 {
+                for x in 0..23 {
+                    assert_eq!(
+                        state.i_value(x, y),
+                        expected.0.values[y * 23 + x],
+                        "copy {} at ({}, {})",
+                        n,
+                        x,
+                        y
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_shifts<T, S>()
+    where
+        T: D2ArrayLike + MbState,
+        S: Solver<T> + Default,
+    {
+        let solver = S::default();
+        let solve = |w: i64, h: i64| -> T { solver.solve(Viewbox::initial(w, h).into()) };
+        // Shifts past the whole state only keep the part of the fill that
+        // lands in it.
+        for shift in [-9i64, -5, -2, 3, 5, 8] {
+            let mut state = solve(7, 5);
+            let mut expected = Pixels::of(&state);
+            let rows = solve(7, shift.abs());
+            state.shift_rows(shift, Some(&rows));
+            expected.shift_rows(shift, Some(&Pixels::of(&rows)));
+            assert_eq!(
+                Pixels::of(&state).0.values,
+                expected.0.values,
+                "rows shifted by {}",
+                shift
+            );
+
+            let mut state = solve(5, 7);
+            let mut expected = Pixels::of(&state);
+            let cols = solve(shift.abs(), 7);
+            state.shift_cols(shift, Some(&cols));
+            expected.shift_cols(shift, Some(&Pixels::of(&cols)));
+            assert_eq!(
+                Pixels::of(&state).0.values,
+                expected.0.values,
+                "cols shifted by {}",
+                shift
+            );
+        }
+    }
+
+    fn check_tiles<T, S>()
+    where
+        T: D2ArrayLike + MbState,
+        S: Solver<T> + Default,
+    {
+        let solver = S::default();
+        let viewbox = Viewbox::initial(23, 11);
+        let whole = || -> T { solver.solve(viewbox.into()) };
+        let expected = Pixels::of(&whole());
+        for (w, h) in [(1, 1), (4, 3), (5, 11), (23, 2), (30, 30)] {
+            let tiles = whole().split_tiles(w, h);
+            assert_eq!(tiles.len(), 23usize.div_ceil(w) * 11usize.div_ceil(h));
+            let joined = T::join_tiles(23, 11, tiles.iter().map(Tile::as_ref));
+            assert_eq!(Pixels::of(&joined).0.values, expected.0.values);
+        }
+
+        // Region of interest: tiles of the whole, placed relative to a window.
+        let tiles = whole().split_tiles(8, 8);
+        let shifted = tiles.iter().map(|tile| {
+            let origin = tile.origin.add(&Point::new(-5, -3));
+            Tile::new(origin, &tile.part)
+        });
+        let window = T::join_tiles(10, 6, shifted);
+        for y in 0..6 {
+            for x in 0..10 {
+                assert_eq!(
+                    window.i_value(x, y),
+                    expected.0.values[(y + 3) * 23 + x + 5]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_tiles() {
+        check_tiles::<VecState, VecSolver>();
+        check_tiles::<SharedVecState, VecSolver>();
+        check_tiles::<ArrayState, ArraySolver>();
+        check_tiles::<SimdVecState, SimdVecSolver>();
+
+        let coords = Viewbox::initial(7, 5).generate_complex_coordinates();
+        let tiles = coords.clone().split_tiles(3, 2);
+        let joined = Coords::join_tiles(7, 5, tiles.iter().map(Tile::as_ref));
+        assert_eq!(joined.values, coords.values);
+    }
+
+    #[test]
+    fn test_block_copies() {
+        check_block_copies::<VecState, VecSolver>();
+        check_block_copies::<SharedVecState, VecSolver>();
+        check_block_copies::<ArrayState, ArraySolver>();
+        check_block_copies::<SimdVecState, SimdVecSolver>();
+    }
+
+    #[test]
+    fn test_shifts() {
+        check_shifts::<VecState, VecSolver>();
+        check_shifts::<SharedVecState, VecSolver>();
+        check_shifts::<ArrayState, ArraySolver>();
+        check_shifts::<SimdVecState, SimdVecSolver>();
+    }
+
+    #[test]
+    fn test_lazy_split() {
+        let viewbox = Viewbox::initial(12, 9);
+        let stack = vec![viewbox, viewbox.region(0, 2, 12, 4)];
+        let whole: VecState = Lazy::Pending(stack.clone()).force();
+        for n in [1, 2, 5, 13, 20] {
+            let parts: Vec<VecState> = Lazy::<VecState>::Pending(stack.clone())
+                .split_to_vec(n)
+                .into_iter()
+                .map(Lazy::force)
+                .collect();
+            assert_eq!(parts.len(), n);
+            let joined = VecState::join_vec(parts);
+            assert_eq!(joined.height, 13);
+            for (a, b) in whole.state.iter().zip(joined.state.iter()) {
+                assert_eq!(a.c, b.c);
+            }
+        }
+    }
+
+    #[test]
+    fn test_julia() {
+        // The Julia set of c = 0 is the unit disk, whichever solver draws it.
+        let viewbox = Viewbox::initial(48, 32);
+        let disk = Formula::Julia { re: 0.0, im: 0.0 };
+        let coords: VecState = viewbox.into();
+        let check = |pixels: Pixels| {
+            for (cell, &i) in coords.state.iter().zip(pixels.0.values.iter()) {
+                if cell.c.norm() < 0.9 {
+                    assert_eq!(i, -1);
+                } else if cell.c.norm() > 1.1 {
+                    assert!(i >= 0);
+                }
+            }
+        };
+        let vec: VecState = VecSolver::default()
+            .with_formula(disk)
+            .solve(viewbox.into());
+        check(Pixels::of(&vec));
+        let array: ArrayState = ArraySolver::default()
+            .with_formula(disk)
+            .solve(viewbox.into());
+        check(Pixels::of(&array));
+        let simd: SimdVecState = SimdVecSolver::default()
+            .with_formula(disk)
+            .solve(viewbox.into());
+        check(Pixels::of(&simd));
+
+        let mandelbrot: VecState = VecSolver::default().solve(viewbox.into());
+        assert_ne!(Pixels::of(&vec).0.values, Pixels::of(&mandelbrot).0.values);
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use rayon::ThreadPool;
+
+use crate::solver::{Lazy, MbState, Solver};
+use crate::threads::{CancelToken, Cancelled, Join, Progress, Split, DEFAULT_CHUNKS_PER_WORKER};
+
+/// A solver run on a rayon pool, instead of threads of our own like
+/// `Solver::threaded`.
+///
+/// States are split into parts like for a `WorkerPool`, and the parts are
+/// solved by the pool's threads, so an application that already has a rayon
+/// pool doesn't oversubscribe the machine.
+pub struct RayonSolver<S> {
+    solver: S,
+    pool: Option<Arc<ThreadPool>>,
+    chunks_per_thread: usize,
+    progress: Progress,
+}
+
+impl<S> RayonSolver<S> {
+    /// Solve on the global rayon pool.
+    pub fn new(solver: S) -> Self {
+        Self {
+            solver,
+            pool: None,
+            chunks_per_thread: DEFAULT_CHUNKS_PER_WORKER,
+            progress: Progress::new(),
+        }
+    }
+
+    /// Solve on `pool` rather than the global pool.
+    pub fn with_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Split each state into `n` parts per thread of the pool.
+    pub fn with_chunks_per_thread(mut self, n: usize) -> Self {
+        assert!(n > 0, "need at least one chunk per thread");
+        self.chunks_per_thread = n;
+        self
+    }
+
+    pub fn threads(&self) -> usize {
+        match self.pool {
+            Some(ref pool) => pool.current_num_threads(),
+            None => rayon::current_num_threads(),
+        }
+    }
+
+    pub fn chunks_per_thread(&self) -> usize {
+        self.chunks_per_thread
+    }
+
+    fn install<R, F>(&self, f: F) -> R
+    where
+        R: Send,
+        F: FnOnce() -> R + Send,
+    {
+        match self.pool {
+            Some(ref pool) => pool.install(f),
+            None => f(),
+        }
+    }
+}
+
+impl<S, T> Solver<T> for RayonSolver<S>
+where
+    S: Solver<T> + Sync,
+    T: MbState + Split + Join + Send,
+{
+    fn solve(&self, state: T) -> T {
+        self.solve_lazy(Lazy::Ready(state))
+    }
+
+    /// Pending states are split before they are built, so each part computes
+    /// its own coordinates.
+    fn solve_lazy(&self, state: Lazy<T>) -> T {
+        self.solve_lazy_cancellable(state, &CancelToken::new())
+            .unwrap()
+    }
+
+    fn solve_cancellable(&self, state: T, cancel: &CancelToken) -> Result<T, Cancelled> {
+        self.solve_lazy_cancellable(Lazy::Ready(state), cancel)
+    }
+
+    fn solve_lazy_cancellable(&self, state: Lazy<T>, cancel: &CancelToken) -> Result<T, Cancelled> {
+        cancel.check()?;
+        let parts = state.split_to_vec(self.threads() * self.chunks_per_thread);
+        self.progress.start(parts.len());
+        let solved = self.install(|| {
+            parts
+                .into_par_iter()
+                .map(|part| {
+                    let solved = self.solver.solve_lazy_cancellable(part, cancel);
+                    self.progress.advance(1);
+                    solved
+                })
+                .collect::<Result<Vec<T>, Cancelled>>()
+        })?;
+        Ok(T::join_vec(solved))
+    }
+
+    fn progress(&self) -> Option<Progress> {
+        Some(self.progress.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::coord::Viewbox;
+    use crate::solver::{SharedVecState, SimdVecSolver, SimdVecState, VecSolver, VecState};
+    use rayon::ThreadPoolBuilder;
+
+    #[test]
+    fn test_rayon_solver() {
+        let viewbox = Viewbox::initial(45, 31);
+        let expected: VecState = VecSolver::default().solve(viewbox.into());
+
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(3).build().unwrap());
+        let solver = RayonSolver::new(VecSolver::default()).with_pool(pool);
+        assert_eq!(solver.threads(), 3);
+        let solved: VecState = solver.solve_lazy(Lazy::viewbox(viewbox));
+        for (a, b) in expected.state.iter().zip(solved.state.iter()) {
+            assert_eq!(a.i, b.i);
+        }
+        let report = Solver::<VecState>::progress(&solver).unwrap().report();
+        assert!(report.is_finished());
+        assert_eq!(report.total, 3 * DEFAULT_CHUNKS_PER_WORKER);
+
+        let solved: SharedVecState = RayonSolver::new(VecSolver::default()).solve(viewbox.into());
+        for (a, b) in expected.state.iter().zip(solved.state.iter()) {
+            assert_eq!(a.i, b.i);
+        }
+        let expected: SimdVecState = SimdVecSolver::default().solve(viewbox.into());
+        let solved: SimdVecState = RayonSolver::new(SimdVecSolver::default())
+            .with_chunks_per_thread(1)
+            .solve(viewbox.into());
+        for y in 0..31 {
+            for x in 0..45 {
+                assert_eq!(solved.i_value(x, y), expected.i_value(x, y));
+            }
+        }
+
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let solver = RayonSolver::new(VecSolver::default());
+        let solved: Result<VecState, _> =
+            solver.solve_lazy_cancellable(Lazy::viewbox(viewbox), &cancel);
+        assert!(solved.is_err());
+    }
+}
@@ -1,274 +1,192 @@
-use std::cell::{Cell, Ref, RefCell, RefMut};
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 
 use crate::threads::RangeSplitter;
 
-#[derive(Debug)]
-pub struct SArray<T, const N: usize>
-where
-    T: Copy + Debug,
-{
-    parent: Option<Arc<SArray<T, N>>>,
-    data: Arc<[Cell<T>; N]>,
-    start: usize,
-    end: usize,
+/// Heap buffer that is only ever accessed through disjoint ranges.
+struct Buffer<T> {
+    ptr: *mut T,
+    len: usize,
 }
 
-unsafe impl<T, const N: usize> Send for SArray<T, N> where T: Copy + Debug {}
-
-impl<T, const N: usize> SArray<T, N>
-where
-    T: Copy + Debug,
-{
-    pub fn full(v: T) -> Self {
-        let data: [Cell<T>; N] = [v; N].map(|v| Cell::new(v));
-        Self {
-            parent: None,
-            data: Arc::new(data),
-            start: 0,
-            end: N,
-        }
-    }
-
-    fn cell(&self, n: usize) -> &Cell<T> {
-        let n = n + self.start;
-        assert!(n <= self.end, "out of bounds");
-        &self.data[n]
-    }
-
-    pub fn get(&self, n: usize) -> T {
-        self.cell(n).get()
-    }
+// SAFETY: the buffer itself hands out no access to its elements; each element
+// is only reached through the one `SArray` whose range contains it, which moves
+// between threads like the element would. Sharing the buffer (the `Arc`) is
+// therefore as safe as sending the elements.
+unsafe impl<T: Send> Send for Buffer<T> {}
+unsafe impl<T: Send> Sync for Buffer<T> {}
 
-    pub fn set(&mut self, n: usize, value: T) {
-        self.cell(n).set(value);
+impl<T> Buffer<T> {
+    fn new(values: Vec<T>) -> Self {
+        let values: Box<[T]> = values.into_boxed_slice();
+        let len = values.len();
+        let ptr = Box::into_raw(values) as *mut T;
+        Self { ptr, len }
     }
 }
 
-impl<T, const N: usize> Default for SArray<T, N>
-where
-    T: Copy + Default + Debug,
-{
-    fn default() -> Self {
-        Self::full(T::default())
+impl<T> Drop for Buffer<T> {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` and `len` come from the boxed slice leaked in `new`, and
+        // no `SArray` borrowing from it is left once the last `Arc` is dropped.
+        unsafe {
+            drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                self.ptr, self.len,
+            )));
+        }
     }
 }
 
-#[derive(Debug)]
-pub struct SRefArray<T, const N: usize>
-where
-    T: Debug,
-{
-    parent: Option<Arc<SRefArray<T, N>>>,
-    data: Arc<[RefCell<T>; N]>,
+/// A range of a buffer shared with other, disjoint, ranges.
+///
+/// Splitting a range gives each part exclusive access to its own elements, so
+/// the parts can be mutated in place from different threads, then joined back
+/// without copying. Ranges can only be made by splitting, which keeps them
+/// disjoint.
+pub struct SArray<T> {
+    data: Arc<Buffer<T>>,
     start: usize,
     end: usize,
+    // An `SArray` hands out `&[T]` and `&mut [T]`, so it is only `Sync` and
+    // `Send` if `T` is.
+    _marker: PhantomData<T>,
 }
 
-unsafe impl<T, const N: usize> Send for SRefArray<T, N> where T: Debug {}
+impl<T> SArray<T> {
+    fn with_range(data: Arc<Buffer<T>>, start: usize, end: usize) -> Self {
+        Self {
+            data,
+            start,
+            end,
+            _marker: PhantomData,
+        }
+    }
 
-impl<T, const N: usize> SRefArray<T, N>
-where
-    T: Debug,
-{
-    pub fn full(v: T) -> Self
+    pub fn full(v: T, len: usize) -> Self
     where
         T: Clone,
     {
-        let data: [RefCell<T>; N] = [(); N].map(|_| RefCell::new(v.clone()));
-        Self {
-            parent: None,
-            data: Arc::new(data),
-            start: 0,
-            end: N,
-        }
+        vec![v; len].into()
     }
 
-    fn cell(&self, n: usize) -> &RefCell<T> {
-        let n = n + self.start;
-        assert!(n <= self.end, "out of bounds");
-        &self.data[n]
+    pub fn len(&self) -> usize {
+        self.end - self.start
     }
 
-    pub fn borrow(&self, n: usize) -> Ref<T> {
-        self.cell(n).borrow()
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
-    pub fn borrow_mut(&mut self, n: usize) -> RefMut<T> {
-        self.cell(n).borrow_mut()
+    /// Split into `n` consecutive ranges, without copying.
+    pub fn split(self, n: usize) -> Vec<Self> {
+        RangeSplitter::split(self.start, self.end, n)
+            .map(|(start, end)| Self::with_range(self.data.clone(), start, end))
+            .collect()
     }
 
-    pub fn get(&self, n: usize) -> T
+    /// Split into ranges of the given lengths, without copying.
+    pub fn split_lengths<I>(self, lengths: I) -> Vec<Self>
     where
-        T: Clone,
+        I: IntoIterator<Item = usize>,
     {
-        self.borrow(n).clone()
+        let mut start = self.start;
+        let parts: Vec<Self> = lengths
+            .into_iter()
+            .map(|len| {
+                let part = Self::with_range(self.data.clone(), start, start + len);
+                start += len;
+                part
+            })
+            .collect();
+        assert_eq!(start, self.end, "lengths must add up to the array length");
+        parts
     }
 
-    pub fn set(&mut self, n: usize, v: T) {
-        *self.cell(n).borrow_mut() = v;
+    /// Whether `parts` are consecutive ranges of the same buffer, in order.
+    fn contiguous(parts: &[Self]) -> bool {
+        parts
+            .windows(2)
+            .all(|pair| Arc::ptr_eq(&pair[0].data, &pair[1].data) && pair[0].end == pair[1].start)
     }
 
-    pub fn take(&mut self, n: usize) -> T
+    /// Join parts back into one array.
+    ///
+    /// Consecutive ranges of the same buffer are joined without copying; other
+    /// parts are copied into a new buffer.
+    pub fn join(parts: Vec<Self>) -> Self
     where
-        T: Default,
+        T: Clone,
     {
-        self.cell(n).take()
-    }
-
-    pub fn swap(&mut self, n1: usize, n2: usize) {
-        self.cell(n1).swap(self.cell(n2))
-    }
-
-    pub fn replace(&mut self, n: usize, v: T) -> T {
-        self.cell(n).replace(v)
+        assert!(!parts.is_empty(), "no parts to join");
+        if Self::contiguous(&parts) {
+            let start = parts[0].start;
+            let end = parts[parts.len() - 1].end;
+            let data = parts[0].data.clone();
+            return Self::with_range(data, start, end);
+        }
+        let len = parts.iter().map(Self::len).sum();
+        let mut values = Vec::with_capacity(len);
+        for part in &parts {
+            values.extend_from_slice(part);
+        }
+        values.into()
     }
 }
 
-impl<T, const N: usize> Default for SRefArray<T, N>
-where
-    T: Copy + Default + std::fmt::Debug,
-{
-    fn default() -> Self {
-        Self::full(T::default())
+impl<T> From<Vec<T>> for SArray<T> {
+    fn from(values: Vec<T>) -> Self {
+        let len = values.len();
+        Self::with_range(Arc::new(Buffer::new(values)), 0, len)
     }
 }
 
-trait SArrayLike<T>
-where
-    T: Clone,
-{
-    fn new(parent: Option<Arc<Self>>, data: T, start: usize, end: usize) -> Self;
-    fn into_parent(self) -> Option<Arc<Self>>;
-    fn start(&self) -> usize;
-    fn end(&self) -> usize;
-    fn data(&self) -> &T;
-}
+impl<T> Deref for SArray<T> {
+    type Target = [T];
 
-impl<T, const N: usize> SArrayLike<Arc<[Cell<T>; N]>> for SArray<T, N>
-where
-    T: Copy + Debug,
-{
-    fn new(parent: Option<Arc<Self>>, data: Arc<[Cell<T>; N]>, start: usize, end: usize) -> Self {
-        Self {
-            parent,
-            data,
-            start,
-            end,
-        }
-    }
-    fn into_parent(self) -> Option<Arc<Self>> {
-        self.parent
-    }
-    fn start(&self) -> usize {
-        self.start
-    }
-    fn end(&self) -> usize {
-        self.end
-    }
-    fn data(&self) -> &Arc<[Cell<T>; N]> {
-        &self.data
+    fn deref(&self) -> &[T] {
+        // SAFETY: `start..end` is within the buffer, and no other `SArray`
+        // overlaps it, so nothing mutates it while `self` is borrowed.
+        unsafe { std::slice::from_raw_parts(self.data.ptr.add(self.start), self.len()) }
     }
 }
 
-impl<T, const N: usize> SArrayLike<Arc<[RefCell<T>; N]>> for SRefArray<T, N>
-where
-    T: Debug,
-{
-    fn new(
-        parent: Option<Arc<Self>>,
-        data: Arc<[RefCell<T>; N]>,
-        start: usize,
-        end: usize,
-    ) -> Self {
-        Self {
-            parent,
-            data,
-            start,
-            end,
-        }
-    }
-    fn into_parent(self) -> Option<Arc<Self>> {
-        self.parent
-    }
-    fn start(&self) -> usize {
-        self.start
-    }
-    fn end(&self) -> usize {
-        self.end
-    }
-    fn data(&self) -> &Arc<[RefCell<T>; N]> {
-        &self.data
+impl<T> DerefMut for SArray<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // SAFETY: as for `deref`, and `self` is borrowed mutably.
+        unsafe { std::slice::from_raw_parts_mut(self.data.ptr.add(self.start), self.len()) }
     }
 }
 
-pub trait SArrayLen<T> {
-    fn len(&self) -> usize;
-    fn is_empty(&self) -> bool;
-}
-
-impl<T, D> SArrayLen<D> for T
+impl<T> Clone for SArray<T>
 where
-    T: SArrayLike<D> + Debug,
-    D: Clone,
+    T: Clone,
 {
-    fn len(&self) -> usize {
-        self.end() - self.start()
-    }
-
-    fn is_empty(&self) -> bool {
-        self.len() == 0
+    /// Copy the range into a buffer of its own.
+    fn clone(&self) -> Self {
+        self.to_vec().into()
     }
 }
 
-pub trait SArraySplit<T>
+impl<T> Debug for SArray<T>
 where
-    Self: Sized,
-{
-    fn split(self, n: usize) -> Vec<Self>;
-    fn join(v: Vec<Self>) -> Self;
-}
-
-impl<T, D> SArraySplit<D> for T
-where
-    T: SArrayLike<D> + Debug,
-    D: Clone,
+    T: Debug,
 {
-    fn split(self, n: usize) -> Vec<T> {
-        let start = self.start();
-        let end = self.end();
-        let data = self.data().clone();
-        let parent = Arc::new(self);
-        let mut parts = vec![];
-        for (m, n) in RangeSplitter::split(start, end, n) {
-            parts.push(T::new(Some(parent.clone()), data.clone(), m, n));
-        }
-        parts
-    }
-
-    fn join(mut v: Vec<T>) -> T {
-        let first = v.pop().unwrap();
-        let parent = first.into_parent().take().unwrap();
-        for other in v {
-            let other_parent = other.into_parent().take().unwrap();
-            assert!(Arc::ptr_eq(&parent, &other_parent));
-            drop(other_parent);
-        }
-        Arc::try_unwrap(parent).expect("more children?")
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{SArray, SArraySplit, SRefArray};
-    use num::complex::Complex;
+    use super::SArray;
     use std::sync::{Arc, Barrier};
     use std::thread;
 
     #[test]
     fn test_sarray() {
-        let sarray: SArray<i32, 12> = SArray::default();
+        let sarray: SArray<i32> = SArray::full(0, 12);
         let mut v = sarray.split(3);
         let mut sc = v.pop().unwrap();
         let sb = v.pop().unwrap();
@@ -281,34 +199,34 @@ mod test {
 
         let t_barrier = barrier.clone();
         let handle_sa = thread::spawn(move || {
-            sa.set(0, 0);
+            sa[0] = 0;
             t_barrier.wait();
-            sa.set(1, 1);
-            sa.set(2, 2);
-            sa.set(3, 3);
+            sa[1] = 1;
+            sa[2] = 2;
+            sa[3] = 3;
             sa
         });
         let t_barrier = barrier.clone();
         let handle_sba = thread::spawn(move || {
             t_barrier.wait();
-            sba.set(0, 4);
-            sba.set(1, 5);
+            sba[0] = 4;
+            sba[1] = 5;
             sba
         });
         let t_barrier = barrier.clone();
         let handle_sbb = thread::spawn(move || {
             t_barrier.wait();
-            sbb.set(0, 6);
-            sbb.set(1, 7);
+            sbb[0] = 6;
+            sbb[1] = 7;
             sbb
         });
         let t_barrier = barrier.clone();
         let handle_sc = thread::spawn(move || {
-            sc.set(0, 8);
+            sc[0] = 8;
             t_barrier.wait();
-            sc.set(1, 9);
-            sc.set(2, 10);
-            sc.set(3, 11);
+            sc[1] = 9;
+            sc[2] = 10;
+            sc[3] = 11;
             sc
         });
 
@@ -320,72 +238,29 @@ mod test {
         let sb = SArray::join(vec![sba, sbb]);
         let sarray = SArray::join(vec![sa, sb, sc]);
 
-        for i in 0..11 {
-            assert_eq!(sarray.get(i), i as i32);
+        assert_eq!(sarray.len(), 12);
+        for i in 0..12 {
+            assert_eq!(sarray[i], i as i32);
         }
     }
 
     #[test]
-    fn test_srefarray() {
-        let sarray: SRefArray<i32, 12> = SRefArray::default();
-        let mut v = sarray.split(3);
-        let mut sc = v.pop().unwrap();
-        let sb = v.pop().unwrap();
-        let mut sa = v.pop().unwrap();
-        let mut vb = sb.split(2);
-        let mut sbb = vb.pop().unwrap();
-        let mut sba = vb.pop().unwrap();
-
-        let barrier = Arc::new(Barrier::new(4));
-
-        let t_barrier = barrier.clone();
-        let handle_sa = thread::spawn(move || {
-            sa.set(0, 0);
-            t_barrier.wait();
-            sa.set(1, 1);
-            sa.set(2, 2);
-            sa.set(3, 3);
-            sa
-        });
-        let t_barrier = barrier.clone();
-        let handle_sba = thread::spawn(move || {
-            t_barrier.wait();
-            sba.set(0, 4);
-            sba.set(1, 5);
-            sba
-        });
-        let t_barrier = barrier.clone();
-        let handle_sbb = thread::spawn(move || {
-            t_barrier.wait();
-            sbb.set(0, 6);
-            sbb.set(1, 7);
-            sbb
-        });
-        let t_barrier = barrier.clone();
-        let handle_sc = thread::spawn(move || {
-            sc.set(0, 8);
-            t_barrier.wait();
-            sc.set(1, 9);
-            sc.set(2, 10);
-            sc.set(3, 11);
-            sc
-        });
-
-        let sa = handle_sa.join().unwrap();
-        let sba = handle_sba.join().unwrap();
-        let sbb = handle_sbb.join().unwrap();
-        let sc = handle_sc.join().unwrap();
-
-        let sb = SRefArray::join(vec![sba, sbb]);
-        let sarray = SRefArray::join(vec![sa, sb, sc]);
-
-        for i in 0..11 {
-            assert_eq!(sarray.get(i), i as i32);
-        }
+    fn test_sarray_join_without_copy() {
+        let sarray: SArray<u8> = (0..10).collect::<Vec<u8>>().into();
+        let ptr = sarray.as_ptr();
+        let parts = sarray.split_lengths([3, 0, 7]);
+        let joined = SArray::join(parts);
+        assert_eq!(joined.as_ptr(), ptr);
+        assert_eq!(&joined[..], &(0..10).collect::<Vec<u8>>()[..]);
     }
 
     #[test]
-    fn test_sarray_complex() {
-        let carr: SArray<Complex<f64>, 4000> = SArray::default();
+    fn test_sarray_join_copies_foreign_parts() {
+        let a: SArray<u8> = vec![1, 2].into();
+        let b: SArray<u8> = vec![3].into();
+        let mut parts = SArray::join(vec![a, b]).split(2);
+        parts.reverse();
+        let joined = SArray::join(parts);
+        assert_eq!(&joined[..], &[3, 1, 2]);
     }
 }
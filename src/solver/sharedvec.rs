@@ -0,0 +1,186 @@
+use crate::complex::*;
+use crate::coord::{Coords, Point, Viewbox};
+use crate::solver::sarray::SArray;
+use crate::solver::vec::{VecCell, VecSolver, VecState};
+use crate::solver::{MbState, Solver};
+use crate::threads::{CancelToken, Cancelled, Join, RangeSplitter, Split};
+
+use super::{copy_cells_from, copy_cells_self, D2ArrayLike, RowMajor};
+
+/// Like `VecState`, but split into row ranges of one shared buffer.
+///
+/// Workers solve their rows in place, and joining the parts back is free as
+/// long as they come from splitting the same state, as when a built state is
+/// solved by a worker pool.
+#[derive(Clone, Debug)]
+pub struct SharedVecState {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) state: SArray<VecCell>,
+}
+
+impl From<VecState> for SharedVecState {
+    fn from(v: VecState) -> Self {
+        Self {
+            width: v.width,
+            height: v.height,
+            state: v.state.into(),
+        }
+    }
+}
+
+impl From<Coords<C<f64>>> for SharedVecState {
+    fn from(v: Coords<C<f64>>) -> Self {
+        VecState::from(v).into()
+    }
+}
+
+impl From<Viewbox> for SharedVecState {
+    fn from(v: Viewbox) -> Self {
+        VecState::from(v).into()
+    }
+}
+
+impl MbState for SharedVecState {
+    const BUILD_BEFORE_SPLIT: bool = true;
+
+    fn height(&self) -> usize {
+        self.height
+    }
+    fn width(&self) -> usize {
+        self.width
+    }
+    fn i_value(&self, x: usize, y: usize) -> i16 {
+        self.state[y * self.width + x].i
+    }
+}
+
+impl Split for SharedVecState {
+    fn split_to_vec(self, n: usize) -> Vec<Self> {
+        let width = self.width;
+        let heights: Vec<usize> = RangeSplitter::split(0, self.height, n)
+            .map(|(start, end)| end - start)
+            .collect();
+        let parts = self.state.split_lengths(heights.iter().map(|h| h * width));
+        parts
+            .into_iter()
+            .zip(heights)
+            .map(|(state, height)| Self {
+                width,
+                height,
+                state,
+            })
+            .collect()
+    }
+}
+
+impl Join for SharedVecState {
+    fn join_vec(parts: Vec<Self>) -> Self {
+        let width = parts[0].width;
+        let mut height = 0;
+        let mut state_parts = vec![];
+        for part in parts {
+            assert!(part.width == width);
+            height += part.height;
+            state_parts.push(part.state);
+        }
+        Self {
+            width,
+            height,
+            state: SArray::join(state_parts),
+        }
+    }
+}
+
+impl D2ArrayLike for SharedVecState {
+    fn new(width: usize, height: usize) -> Self {
+        VecState::new(width, height).into()
+    }
+    fn width(&self) -> usize {
+        self.width
+    }
+    fn height(&self) -> usize {
+        self.height
+    }
+    fn copy_from(&mut self, other: &Self, from: Point<usize>, to: Point<usize>) {
+        self.state[to.row_idx(self.width)] = other.state[from.row_idx(other.width)];
+    }
+    fn copy_self(&mut self, from: Point<usize>, to: Point<usize>) {
+        self.state[to.row_idx(self.width)] = self.state[from.row_idx(self.width)];
+    }
+    fn copy_span_from(&mut self, other: &Self, from: Point<usize>, to: Point<usize>, len: usize) {
+        copy_cells_from(self, other, from, to, len);
+    }
+    fn copy_span_self(&mut self, from: Point<usize>, to: Point<usize>, len: usize) {
+        copy_cells_self(self, from, to, len);
+    }
+    fn row_contiguous(&self) -> bool {
+        true
+    }
+}
+
+impl RowMajor for SharedVecState {
+    type Cell = VecCell;
+
+    fn cells(&self) -> &[VecCell] {
+        &self.state
+    }
+    fn cells_mut(&mut self) -> &mut [VecCell] {
+        &mut self.state
+    }
+}
+
+impl Solver<SharedVecState> for VecSolver {
+    fn solve(&self, state: SharedVecState) -> SharedVecState {
+        self.solve_cancellable(state, &CancelToken::new()).unwrap()
+    }
+
+    fn solve_cancellable(
+        &self,
+        mut state: SharedVecState,
+        cancel: &CancelToken,
+    ) -> Result<SharedVecState, Cancelled> {
+        self.solve_cells(&mut state.state, cancel)?;
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::solver::Lazy;
+
+    #[test]
+    fn test_shared_vec_solve_in_place() {
+        let viewbox = Viewbox::initial(31, 17);
+        let expected: VecState = VecSolver::default().solve(viewbox.into());
+
+        let state = SharedVecState::from(viewbox);
+        let ptr = state.state.as_ptr();
+        let solved = VecSolver::default().threaded(4).solve(state);
+        assert_eq!(solved.state.as_ptr(), ptr);
+        assert_eq!((solved.width, solved.height), (31, 17));
+        for (a, b) in expected.state.iter().zip(solved.state.iter()) {
+            assert_eq!(a.i, b.i);
+        }
+
+        // A pending state is built in one buffer, which its parts join back to.
+        let parts: Vec<SharedVecState> = Lazy::viewbox(viewbox)
+            .split_to_vec(4)
+            .into_iter()
+            .map(|part| match part {
+                Lazy::Ready(part) => part,
+                Lazy::Pending(_) => panic!("shared state split before it was built"),
+            })
+            .collect();
+        let ptr = parts[0].state.as_ptr();
+        assert_eq!(SharedVecState::join_vec(parts).state.as_ptr(), ptr);
+
+        let solved: SharedVecState = VecSolver::default()
+            .threaded(4)
+            .solve_lazy(Lazy::viewbox(viewbox));
+        for (a, b) in expected.state.iter().zip(solved.state.iter()) {
+            assert_eq!(a.i, b.i);
+        }
+    }
+}
@@ -3,7 +3,10 @@ use ultraviolet::{f64x4, DMat2x4, DVec2x4};
 use wide::CmpGt;
 
 use crate::complex::C;
-use crate::{coord::Coords, Join, MbState, Solver, Split};
+use crate::coord::{Coords, Point, Viewbox};
+use crate::solver::{D2ArrayLike, Formula};
+use crate::threads::{CancelToken, Cancelled};
+use crate::{Join, MbState, Solver, Split};
 
 lazy_static! {
     static ref INF: f64x4 = f64x4::splat(f64::INFINITY);
@@ -46,7 +49,15 @@ pub fn c4tostr(c: C4) -> String {
     )
 }
 
-#[derive(Debug, Clone)]
+/// Number of f64 lanes in a SIMD cell.
+pub const LANES: usize = 4;
+
+/// Number of cells needed for a row of the given width.
+fn row_cells(width: usize) -> usize {
+    width.div_ceil(LANES)
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct SimdVecCell {
     pub(crate) c: C4,
     pub(crate) z: C4,
@@ -54,6 +65,11 @@ pub struct SimdVecCell {
 }
 
 impl SimdVecCell {
+    fn new(re: [f64; LANES], im: [f64; LANES]) -> Self {
+        let c = c4(f64x4::new(re), f64x4::new(im));
+        Self { c, z: c, i: *INF }
+    }
+
     pub fn iterate(&mut self, iterations: usize, mut iteration: f64x4, treshold: f64x4) {
         for _ in 0..iterations {
             iteration += *ONE;
@@ -64,8 +80,61 @@ impl SimdVecCell {
             self.i = self.i.min(diverged_i);
         }
     }
+
+    /// The c, z and i values of a single lane.
+    fn lane(&self, n: usize) -> (C<f64>, C<f64>, f64) {
+        let c = C::new(re4(self.c).as_array_ref()[n], im4(self.c).as_array_ref()[n]);
+        let z = C::new(re4(self.z).as_array_ref()[n], im4(self.z).as_array_ref()[n]);
+        (c, z, self.i.as_array_ref()[n])
+    }
+
+    fn set_lane(&mut self, n: usize, (c, z, i): (C<f64>, C<f64>, f64)) {
+        fn set(c4v: C4, n: usize, v: C<f64>) -> C4 {
+            let mut re = re4(c4v).to_array();
+            let mut im = im4(c4v).to_array();
+            re[n] = v.re;
+            im[n] = v.im;
+            c4(f64x4::new(re), f64x4::new(im))
+        }
+        self.c = set(self.c, n, c);
+        self.z = set(self.z, n, z);
+        let mut iv = self.i.to_array();
+        iv[n] = i;
+        self.i = f64x4::new(iv);
+    }
 }
 
+/// Split a span of `len` pixels, copied from column `from_x` of a row of
+/// `from_width` pixels to column `to_x` of a row of `to_width` pixels, into a head
+/// of single pixels followed by a number of whole cells.
+///
+/// Returns `None` when the source and destination are not at the same lane, in
+/// which case the span has to be copied pixel by pixel. The last cell of a row is
+/// copied whole, padding included, when the span runs to the end of both rows.
+fn span_cells(
+    from_x: usize,
+    from_width: usize,
+    to_x: usize,
+    to_width: usize,
+    len: usize,
+) -> Option<(usize, usize)> {
+    if from_x % LANES != to_x % LANES {
+        return None;
+    }
+    let head = ((LANES - to_x % LANES) % LANES).min(len);
+    let rest = len - head;
+    let cells = if from_x + len == from_width && to_x + len == to_width {
+        row_cells(rest)
+    } else {
+        rest / LANES
+    };
+    Some((head, cells))
+}
+
+/// Mandelbrot state packed into SIMD cells.
+///
+/// Each row starts on a new cell; if the width is not a multiple of `LANES`, the
+/// last cell of each row is padded by repeating the last pixel of the row.
 #[derive(Clone)]
 pub struct SimdVecState {
     pub(crate) width: usize,
@@ -73,6 +142,39 @@ pub struct SimdVecState {
     pub(crate) state: Vec<SimdVecCell>,
 }
 
+impl SimdVecState {
+    /// Cell index and lane of a pixel.
+    fn cell_idx(&self, p: Point<usize>) -> (usize, usize) {
+        (p.y * row_cells(self.width) + p.x / LANES, p.x % LANES)
+    }
+
+    /// Pack a row of coordinates into cells, padding the last one.
+    fn push_row<I>(state: &mut Vec<SimdVecCell>, row: I)
+    where
+        I: Iterator<Item = C<f64>>,
+    {
+        let mut re = [0.0; LANES];
+        let mut im = [0.0; LANES];
+        let mut n = 0;
+        for c in row {
+            re[n] = c.re;
+            im[n] = c.im;
+            n += 1;
+            if n == LANES {
+                state.push(SimdVecCell::new(re, im));
+                n = 0;
+            }
+        }
+        if n > 0 {
+            for k in n..LANES {
+                re[k] = re[n - 1];
+                im[k] = im[n - 1];
+            }
+            state.push(SimdVecCell::new(re, im));
+        }
+    }
+}
+
 impl MbState for SimdVecState {
     fn width(&self) -> usize {
         self.width
@@ -81,8 +183,8 @@ impl MbState for SimdVecState {
         self.height
     }
     fn i_value(&self, x: usize, y: usize) -> i16 {
-        let n = self.width * y + x;
-        let ival = self.state[n / 4].i.as_array_ref()[n % 4];
+        let (n, lane) = self.cell_idx(Point::new(x, y));
+        let ival = self.state[n].i.as_array_ref()[lane];
         if ival == f64::INFINITY {
             -1
         } else {
@@ -93,19 +195,11 @@ impl MbState for SimdVecState {
 
 impl From<Coords<C<f64>>> for SimdVecState {
     fn from(v: Coords<C<f64>>) -> Self {
-        let cs: Vec<num::complex::Complex<f64>> = v.values;
-        assert!(cs.len() % 4 == 0, "oops");
-        let mut state = Vec::with_capacity(cs.len() / 4);
-
-        for i in (0..cs.len()).step_by(4) {
-            let re = [cs[i].re, cs[i + 1].re, cs[i + 2].re, cs[i + 3].re];
-            let im = [cs[i].im, cs[i + 1].im, cs[i + 2].im, cs[i + 3].im];
-            let c = c4(f64x4::new(re), f64x4::new(im));
-            state.push(SimdVecCell {
-                c,
-                z: c,
-                i: f64x4::splat(f64::INFINITY),
-            })
+        let mut state = Vec::with_capacity(row_cells(v.width) * v.height);
+        if v.width > 0 {
+            for row in v.values.chunks(v.width) {
+                Self::push_row(&mut state, row.iter().copied());
+            }
         }
 
         Self {
@@ -116,6 +210,96 @@ impl From<Coords<C<f64>>> for SimdVecState {
     }
 }
 
+impl From<Viewbox> for SimdVecState {
+    fn from(v: Viewbox) -> Self {
+        let width = v.width as usize;
+        let height = v.height as usize;
+        let mut state = Vec::with_capacity(row_cells(width) * height);
+        let mut coordinates = v.coordinates();
+        for _ in 0..height {
+            Self::push_row(&mut state, coordinates.by_ref().take(width));
+        }
+
+        Self {
+            width,
+            height,
+            state,
+        }
+    }
+}
+
+impl D2ArrayLike for SimdVecState {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            state: vec![SimdVecCell::new([0.0; LANES], [0.0; LANES]); row_cells(width) * height],
+        }
+    }
+    fn width(&self) -> usize {
+        self.width
+    }
+    fn height(&self) -> usize {
+        self.height
+    }
+    fn copy_from(&mut self, other: &Self, from: Point<usize>, to: Point<usize>) {
+        let (n_from, lane_from) = other.cell_idx(from);
+        let (n_to, lane_to) = self.cell_idx(to);
+        let lane = other.state[n_from].lane(lane_from);
+        self.state[n_to].set_lane(lane_to, lane);
+    }
+    fn copy_self(&mut self, from: Point<usize>, to: Point<usize>) {
+        let (n_from, lane_from) = self.cell_idx(from);
+        let (n_to, lane_to) = self.cell_idx(to);
+        let lane = self.state[n_from].lane(lane_from);
+        self.state[n_to].set_lane(lane_to, lane);
+    }
+    fn copy_span_from(&mut self, other: &Self, from: Point<usize>, to: Point<usize>, len: usize) {
+        let copy = |s: &mut Self, dx: usize| {
+            s.copy_from(
+                other,
+                Point::new(from.x + dx, from.y),
+                Point::new(to.x + dx, to.y),
+            )
+        };
+        let Some((head, cells)) = span_cells(from.x, other.width, to.x, self.width, len) else {
+            return (0..len).for_each(|dx| copy(self, dx));
+        };
+        (0..head).for_each(|dx| copy(self, dx));
+        let (n_from, _) = other.cell_idx(Point::new(from.x + head, from.y));
+        let (n_to, _) = self.cell_idx(Point::new(to.x + head, to.y));
+        self.state[n_to..n_to + cells].copy_from_slice(&other.state[n_from..n_from + cells]);
+        ((head + cells * LANES).min(len)..len).for_each(|dx| copy(self, dx));
+    }
+    fn copy_span_self(&mut self, from: Point<usize>, to: Point<usize>, len: usize) {
+        let copy = |s: &mut Self, dx: usize| {
+            s.copy_self(Point::new(from.x + dx, from.y), Point::new(to.x + dx, to.y))
+        };
+        // Copy back to front when moving right within a row, like memmove.
+        let backwards = from.y == to.y && to.x > from.x;
+        let Some((head, cells)) = span_cells(from.x, self.width, to.x, self.width, len) else {
+            if backwards {
+                (0..len).rev().for_each(|dx| copy(self, dx));
+            } else {
+                (0..len).for_each(|dx| copy(self, dx));
+            }
+            return;
+        };
+        let tail = (head + cells * LANES).min(len);
+        let (n_from, _) = self.cell_idx(Point::new(from.x + head, from.y));
+        let (n_to, _) = self.cell_idx(Point::new(to.x + head, to.y));
+        if backwards {
+            (tail..len).rev().for_each(|dx| copy(self, dx));
+            self.state.copy_within(n_from..n_from + cells, n_to);
+            (0..head).rev().for_each(|dx| copy(self, dx));
+        } else {
+            (0..head).for_each(|dx| copy(self, dx));
+            self.state.copy_within(n_from..n_from + cells, n_to);
+            (tail..len).for_each(|dx| copy(self, dx));
+        }
+    }
+}
+
 impl Split for SimdVecState {
     fn split_to_vec(self, n: usize) -> Vec<Self> {
         let rows = self.state.split_to_vec(self.height);
@@ -143,7 +327,7 @@ impl Join for SimdVecState {
         for part in parts {
             assert!(part.width == width);
             height += part.height;
-            state_parts.push(part.state.clone());
+            state_parts.push(part.state);
         }
         Self {
             width,
@@ -155,31 +339,64 @@ impl Join for SimdVecState {
 
 #[derive(Clone, Debug)]
 pub struct SimdVecSolver {
+    iterations: u16,
     treshold: f64x4,
+    formula: Formula,
 }
 
-impl Default for SimdVecSolver {
-    fn default() -> Self {
+impl SimdVecSolver {
+    pub fn new(treshold: f64, iterations: u16) -> Self {
         Self {
-            treshold: f64x4::splat(2.0),
+            iterations,
+            treshold: f64x4::splat(treshold),
+            formula: Formula::Mandelbrot,
         }
     }
+
+    pub fn with_formula(mut self, formula: Formula) -> Self {
+        self.formula = formula;
+        self
+    }
+}
+
+impl Default for SimdVecSolver {
+    fn default() -> Self {
+        Self::new(2.0, 100)
+    }
 }
 
 impl Solver<SimdVecState> for SimdVecSolver {
-    fn solve(&self, mut state: SimdVecState) -> SimdVecState {
-        for cell in &mut state.state {
+    fn solve(&self, state: SimdVecState) -> SimdVecState {
+        self.solve_cancellable(state, &CancelToken::new()).unwrap()
+    }
+
+    /// Each cell is iterated to the end in one go, so the token is checked
+    /// between rows.
+    fn solve_cancellable(
+        &self,
+        mut state: SimdVecState,
+        cancel: &CancelToken,
+    ) -> Result<SimdVecState, Cancelled> {
+        let row = row_cells(state.width).max(1);
+        let parameter = self
+            .formula
+            .parameter()
+            .map(|p| c4(f64x4::splat(p.re), f64x4::splat(p.im)));
+        for (n, cell) in state.state.iter_mut().enumerate() {
+            if n % row == 0 {
+                cancel.check()?;
+            }
             let mut iteration = *ZERO;
-            for _ in 0..100 {
+            for _ in 0..self.iterations {
                 iteration += *ONE;
-                cell.z = (cell.z * cell.z) + cell.c;
+                cell.z = (cell.z * cell.z) + parameter.unwrap_or(cell.c);
                 let z_abs = abs(cell.z);
                 let diverged = z_abs.cmp_gt(self.treshold);
                 let diverged_i = diverged.blend(iteration, *INF);
                 cell.i = cell.i.min(diverged_i);
             }
         }
-        state
+        Ok(state)
     }
 }
 
@@ -201,4 +418,42 @@ pub mod test {
         cell2.iterate(100, f64x4::splat(0.0), treshold);
         // println!("{:?} {:?}", cell1.i, cell2.i);
     }
+
+    #[test]
+    fn test_padded_rows() {
+        let viewbox = Viewbox::initial(13, 5);
+        let coords = viewbox.generate_complex_coordinates();
+        let state: SimdVecState = viewbox.into();
+        assert_eq!(state.state.len(), 4 * 5);
+        let from_coords: SimdVecState = coords.clone().into();
+        for y in 0..5 {
+            for x in 0..13 {
+                let (n, lane) = state.cell_idx(Point::new(x, y));
+                let (c, _, _) = state.state[n].lane(lane);
+                assert_eq!(c, coords.values[y * 13 + x]);
+                let (c, _, _) = from_coords.state[n].lane(lane);
+                assert_eq!(c, coords.values[y * 13 + x]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_simd_fast_pan() {
+        use crate::Mandelbrot;
+        let mut fast = Mandelbrot::<SimdVecState>::initialize::<SimdVecSolver>(37, 23);
+        let mut full = Mandelbrot::<SimdVecState>::initialize::<SimdVecSolver>(37, 23);
+        for (x, y) in [(3, 0), (0, -5), (-6, 7), (2, 2)] {
+            fast.pan_fast(x, y);
+            full.pan(x, y);
+            fast.pan_fast_vertical(y);
+            full.pan(0, y);
+            fast.pan_fast_horizontal(x);
+            full.pan(x, 0);
+            for y in 0..23 {
+                for x in 0..37 {
+                    assert_eq!(fast.state.i_value(x, y), full.state.i_value(x, y));
+                }
+            }
+        }
+    }
 }
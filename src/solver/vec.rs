@@ -1,11 +1,11 @@
 use crate::complex::*;
-use crate::coord::{Coords, Point};
-use crate::solver::{MbState, Solver};
-use crate::threads::{Join, Split};
+use crate::coord::{Coords, Point, Viewbox};
+use crate::solver::{Formula, MbState, Solver};
+use crate::threads::{CancelToken, Cancelled, Join, Split};
 
-use super::D2ArrayLike;
+use super::{copy_cells_from, copy_cells_self, D2ArrayLike, RowMajor};
 
-#[derive(Clone, Debug)]
+#[derive(Copy, Clone, Debug)]
 pub struct VecCell {
     pub(crate) c: C<f64>,
     pub(crate) z: C<f64>,
@@ -34,6 +34,20 @@ impl From<Coords<C<f64>>> for VecState {
     }
 }
 
+impl From<Viewbox> for VecState {
+    fn from(v: Viewbox) -> Self {
+        let state: Vec<VecCell> = v
+            .coordinates()
+            .map(|c| VecCell { c, z: c, i: -1 })
+            .collect();
+        Self {
+            width: v.width as usize,
+            height: v.height as usize,
+            state,
+        }
+    }
+}
+
 impl MbState for VecState {
     fn height(&self) -> usize {
         self.height
@@ -73,7 +87,7 @@ impl Join for VecState {
         for part in parts {
             assert!(part.width == width);
             height += part.height;
-            state_parts.push(part.state.clone());
+            state_parts.push(part.state);
         }
         Self {
             width,
@@ -105,10 +119,30 @@ impl D2ArrayLike for VecState {
         self.height
     }
     fn copy_from(&mut self, other: &Self, from: Point<usize>, to: Point<usize>) {
-        self.state[to.row_idx(self.width)] = other.state[from.row_idx(other.width)].clone();
+        self.state[to.row_idx(self.width)] = other.state[from.row_idx(other.width)];
     }
     fn copy_self(&mut self, from: Point<usize>, to: Point<usize>) {
-        self.state[to.row_idx(self.width)] = self.state[from.row_idx(self.width)].clone();
+        self.state[to.row_idx(self.width)] = self.state[from.row_idx(self.width)];
+    }
+    fn copy_span_from(&mut self, other: &Self, from: Point<usize>, to: Point<usize>, len: usize) {
+        copy_cells_from(self, other, from, to, len);
+    }
+    fn copy_span_self(&mut self, from: Point<usize>, to: Point<usize>, len: usize) {
+        copy_cells_self(self, from, to, len);
+    }
+    fn row_contiguous(&self) -> bool {
+        true
+    }
+}
+
+impl RowMajor for VecState {
+    type Cell = VecCell;
+
+    fn cells(&self) -> &[VecCell] {
+        &self.state
+    }
+    fn cells_mut(&mut self) -> &mut [VecCell] {
+        &mut self.state
     }
 }
 
@@ -116,29 +150,62 @@ impl D2ArrayLike for VecState {
 pub struct VecSolver {
     iterations: u16,
     treshold: f64,
+    formula: Formula,
 }
 
 impl Solver<VecState> for VecSolver {
-    fn solve(&self, mut state: VecState) -> VecState {
+    fn solve(&self, state: VecState) -> VecState {
+        self.solve_cancellable(state, &CancelToken::new()).unwrap()
+    }
+
+    fn solve_cancellable(
+        &self,
+        mut state: VecState,
+        cancel: &CancelToken,
+    ) -> Result<VecState, Cancelled> {
+        self.solve_cells(&mut state.state, cancel)?;
+        Ok(state)
+    }
+}
+
+impl VecSolver {
+    pub fn new(treshold: f64, iterations: u16) -> Self {
+        Self {
+            treshold,
+            iterations,
+            formula: Formula::Mandelbrot,
+        }
+    }
+
+    pub fn with_formula(mut self, formula: Formula) -> Self {
+        self.formula = formula;
+        self
+    }
+
+    /// Iterate cells in place, for states that store them as a slice.
+    pub(crate) fn solve_cells(
+        &self,
+        cells: &mut [VecCell],
+        cancel: &CancelToken,
+    ) -> Result<(), Cancelled> {
+        let parameter = self.formula.parameter();
         for iteration in 0..self.iterations {
-            for cell in &mut state.state {
+            cancel.check()?;
+            for cell in cells.iter_mut() {
                 if cell.i == -1 {
-                    cell.z = (cell.z * cell.z) + cell.c;
+                    cell.z = (cell.z * cell.z) + parameter.unwrap_or(cell.c);
                     if cell.z.norm() > self.treshold {
                         cell.i = iteration as i16;
                     }
                 }
             }
         }
-        state
+        Ok(())
     }
 }
 
 impl Default for VecSolver {
     fn default() -> Self {
-        Self {
-            iterations: 100,
-            treshold: 2.0,
-        }
+        Self::new(2.0, 100)
     }
 }
@@ -1,9 +1,227 @@
-use std::iter::zip;
-use std::sync::mpsc;
+use std::any::Any;
+use std::fmt;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::task::{Context, Poll, Waker};
 use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coord::Point;
+
+/// Cooperative cancellation flag, shared between the caller and the work it
+/// started.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// `Err(Cancelled)` if the token was cancelled, for use with `?`.
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The work was abandoned because its `CancelToken` was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Snapshot of the progress of a call.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProgressReport {
+    /// Chunks done so far.
+    pub done: usize,
+    /// Chunks in the call.
+    pub total: usize,
+    /// Time since the call started, or that it took once finished.
+    pub elapsed: Duration,
+}
+
+impl ProgressReport {
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.done as f64 / self.total as f64
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.done >= self.total
+    }
+
+    /// Estimated time left, assuming the remaining chunks take as long as the
+    /// ones done so far.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.done == 0 {
+            return None;
+        }
+        let left = (self.total - self.done) as f64 / self.done as f64;
+        Some(self.elapsed.mul_f64(left))
+    }
+}
+
+impl fmt::Display for ProgressReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>3.0}%", 100.0 * self.fraction())?;
+        match self.eta() {
+            Some(eta) if !self.is_finished() => write!(f, " (ETA {:.1}s)", eta.as_secs_f64()),
+            _ => write!(f, " ({:.1}s)", self.elapsed.as_secs_f64()),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ProgressState {
+    done: usize,
+    total: usize,
+    started: Option<Instant>,
+    finished: Option<Duration>,
+}
+
+/// Progress of the calls to a `WorkerPool`, shared between the pool and
+/// whoever watches it, e.g. from another thread while the call blocks.
+#[derive(Clone, Debug, Default)]
+pub struct Progress(Arc<Mutex<ProgressState>>);
+
+impl Progress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start over for a call of `total` chunks.
+    pub fn start(&self, total: usize) {
+        *self.0.lock().unwrap() = ProgressState {
+            done: 0,
+            total,
+            started: Some(Instant::now()),
+            finished: None,
+        };
+    }
+
+    /// Mark `n` more chunks as done.
+    pub fn advance(&self, n: usize) {
+        let mut state = self.0.lock().unwrap();
+        state.done = (state.done + n).min(state.total);
+        if state.done == state.total {
+            state.finished = state.started.map(|s| s.elapsed());
+        }
+    }
+
+    pub fn report(&self) -> ProgressReport {
+        let state = self.0.lock().unwrap();
+        let elapsed = match (state.finished, state.started) {
+            (Some(finished), _) => finished,
+            (None, Some(started)) => started.elapsed(),
+            (None, None) => Duration::ZERO,
+        };
+        ProgressReport {
+            done: state.done,
+            total: state.total,
+            elapsed,
+        }
+    }
+}
+
+struct OneshotState<T> {
+    value: Option<T>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+/// Sending half of a `oneshot` channel.
+pub struct OneshotSender<T>(Arc<Mutex<OneshotState<T>>>);
+
+/// Receiving half of a `oneshot` channel; a future of the value sent, or
+/// `Err(Cancelled)` if the sender was dropped without sending.
+pub struct OneshotReceiver<T>(Arc<Mutex<OneshotState<T>>>);
+
+/// Channel for a single value, received as a `Future` so that async code can
+/// wait on work done by our threads, whatever executor it runs on.
+pub fn oneshot<T>() -> (OneshotSender<T>, OneshotReceiver<T>) {
+    let state = Arc::new(Mutex::new(OneshotState {
+        value: None,
+        closed: false,
+        waker: None,
+    }));
+    (OneshotSender(state.clone()), OneshotReceiver(state))
+}
+
+impl<T> OneshotSender<T> {
+    pub fn send(self, value: T) {
+        self.0.lock().unwrap().value = Some(value);
+    }
+
+    /// Whether the receiver was dropped, so the value is no longer wanted.
+    pub fn is_closed(&self) -> bool {
+        Arc::strong_count(&self.0) == 1
+    }
+}
+
+impl<T> Drop for OneshotSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock().unwrap();
+        state.closed = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Future for OneshotReceiver<T> {
+    type Output = Result<T, Cancelled>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.0.lock().unwrap();
+        match state.value.take() {
+            Some(value) => Poll::Ready(Ok(value)),
+            None if state.closed => Poll::Ready(Err(Cancelled)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
 
 pub trait Call<T, U> {
     fn call(&self, t: T) -> U;
+
+    /// Like `call`, but give up early once `cancel` is cancelled.
+    ///
+    /// The token is only checked before starting, unless the implementation
+    /// checks it more often.
+    fn call_cancellable(&self, t: T, cancel: &CancelToken) -> Result<U, Cancelled> {
+        cancel.check()?;
+        Ok(self.call(t))
+    }
 }
 
 impl<F, T, U> Call<T, U> for F
@@ -111,8 +329,12 @@ where
 }
 
 impl<T> Join for Vec<T> {
+    /// Append the other parts to the first one, growing its buffer at most once.
     fn join_vec(parts: Vec<Self>) -> Self {
-        let mut v: Vec<T> = vec![];
+        let len: usize = parts.iter().map(Vec::len).sum();
+        let mut parts = parts.into_iter();
+        let mut v: Vec<T> = parts.next().unwrap_or_default();
+        v.reserve(len - v.len());
         for p in parts {
             v.extend(p);
         }
@@ -120,8 +342,70 @@ impl<T> Join for Vec<T> {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct JoinError;
+/// Why split parts could not be joined back together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JoinError {
+    /// There were no parts at all.
+    NoParts,
+    /// Part `n` was given, but there are only `parts` parts.
+    OutOfRange { n: usize, parts: usize },
+    /// Part `n` was given more than once.
+    Duplicate(usize),
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoParts => write!(f, "no parts to join"),
+            Self::OutOfRange { n, parts } => {
+                write!(f, "part {} out of range, there are {} parts", n, parts)
+            }
+            Self::Duplicate(n) => write!(f, "part {} given more than once", n),
+        }
+    }
+}
+
+impl std::error::Error for JoinError {}
+
+/// Why a call to a `WorkerPool` failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolError {
+    /// The pool has no workers to run the call.
+    NoWorkers,
+    /// The call was cancelled before all of its parts were done.
+    Cancelled,
+    /// The worker running part `part` of the call panicked with `message`.
+    Panicked { part: usize, message: String },
+    /// The results of the workers could not be joined.
+    Join(JoinError),
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoWorkers => write!(f, "worker pool has no workers"),
+            Self::Cancelled => write!(f, "call cancelled"),
+            Self::Panicked { part, message } => {
+                write!(f, "worker panicked on part {}: {}", part, message)
+            }
+            Self::Join(e) => write!(f, "could not join results: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PoolError {}
+
+impl From<Cancelled> for PoolError {
+    fn from(_: Cancelled) -> Self {
+        Self::Cancelled
+    }
+}
+
+impl From<JoinError> for PoolError {
+    fn from(e: JoinError) -> Self {
+        Self::Join(e)
+    }
+}
 
 #[derive(Debug)]
 pub struct SplitPart<T> {
@@ -142,15 +426,15 @@ where
     pub fn join(splits: Vec<SplitPart<T>>) -> Result<T, JoinError> {
         let n = splits.len();
         if n == 0 {
-            return Err(JoinError);
+            return Err(JoinError::NoParts);
         }
         let mut parts: Vec<Option<T>> = (0..n).map(|_| None).collect();
         for s in splits {
             if s.n >= n {
-                return Err(JoinError);
+                return Err(JoinError::OutOfRange { n: s.n, parts: n });
             }
             if parts[s.n].is_some() {
-                return Err(JoinError);
+                return Err(JoinError::Duplicate(s.n));
             }
             parts[s.n] = Some(s.part);
         }
@@ -160,38 +444,133 @@ where
     }
 }
 
-struct Worker<T> {
-    param_tx: mpsc::Sender<SplitPart<T>>,
+/// A rectangular part of a 2-D value, with the position of its top-left corner
+/// in the whole.
+///
+/// The position may be negative, or the tile may stick out of the whole, when
+/// tiles on a fixed grid are placed over a region of interest.
+#[derive(Debug, Clone)]
+pub struct Tile<T> {
+    pub origin: Point<i64>,
+    pub part: T,
+}
+
+impl<T> Tile<T> {
+    pub fn new(origin: Point<i64>, part: T) -> Self {
+        Self { origin, part }
+    }
+
+    pub fn as_ref(&self) -> Tile<&T> {
+        Tile::new(self.origin, &self.part)
+    }
+}
+
+/// 2-D values that can be split into rectangular tiles.
+pub trait SplitTiles: Sized {
+    /// Split into tiles of `width` x `height`, in row-major order; the tiles of
+    /// the last column and row are smaller if the size is not a multiple.
+    fn split_tiles(self, width: usize, height: usize) -> Vec<Tile<Self>>;
 }
 
-impl<T> Worker<T>
+/// 2-D values that can be put together from rectangular tiles.
+pub trait JoinTiles: Sized {
+    /// Assemble a `width` x `height` value by placing each tile at its origin.
+    ///
+    /// Tiles are clipped to the whole; later tiles overwrite earlier ones where
+    /// they overlap, and areas covered by no tile are left at their default.
+    fn join_tiles<'a, I>(width: usize, height: usize, tiles: I) -> Self
+    where
+        Self: 'a,
+        I: IntoIterator<Item = Tile<&'a Self>>;
+}
+
+/// Number of chunks per worker a pool splits its input into by default.
+///
+/// Chunks are pulled from a shared queue by whichever worker is idle, so some
+/// chunks taking much longer than others does not leave the rest of the pool
+/// waiting on a single worker.
+pub const DEFAULT_CHUNKS_PER_WORKER: usize = 8;
+
+/// A chunk of a call, with the token that cancels the whole call.
+type Job<T> = (SplitPart<T>, CancelToken);
+type Queue<T> = Arc<Mutex<mpsc::Receiver<Job<T>>>>;
+type Output<U> = SplitPart<Result<U, PoolError>>;
+
+/// Message of a panic payload, as printed by the default panic hook.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+type Handles = Arc<Mutex<Vec<thread::JoinHandle<()>>>>;
+
+/// What a worker thread needs, kept to replace the thread if it panics.
+struct Worker<T, U> {
+    id: usize,
+    queue: Queue<T>,
+    return_tx: mpsc::Sender<Output<U>>,
+    handles: Handles,
+}
+
+impl<T, U> Worker<T, U>
 where
-    T: Split,
+    T: Send + 'static,
+    U: Send + 'static,
 {
-    fn new<F, U>(f: F, return_tx: mpsc::Sender<SplitPart<U>>) -> Self
+    /// Spawn a worker thread running `f` on the chunks of the queue, until the
+    /// queue is closed.
+    ///
+    /// If `f` panics, the panic is reported as the result of its chunk and the
+    /// thread is replaced by a new one, running the same `f`.
+    fn spawn<F>(self, f: F)
     where
         F: Call<T, U> + Send + 'static,
-        T: Split + Send + 'static,
-        U: Join + Send + 'static,
     {
-        let (param_tx, param_rx) = mpsc::channel::<SplitPart<T>>();
+        let handles = self.handles.clone();
+        let handle = thread::Builder::new()
+            .name(format!("mandelox-worker-{}", self.id))
+            .spawn(move || self.run(f))
+            .expect("failed to spawn worker thread");
+        handles.lock().unwrap().push(handle);
+    }
 
-        thread::spawn(move || loop {
-            let splitted = match param_rx.recv() {
-                Ok(s) => s,
+    fn run<F>(self, f: F)
+    where
+        F: Call<T, U> + Send + 'static,
+    {
+        loop {
+            // The lock is released as soon as a chunk is received, so the other
+            // workers can pull from the queue while this one is busy.
+            let received = self.queue.lock().unwrap().recv();
+            let (splitted, cancel) = match received {
+                Ok(job) => job,
                 Err(_) => return,
             };
-            let res = f.call(splitted.part);
-            if return_tx.send(SplitPart::new(res, splitted.n)).is_err() {
+            let n = splitted.n;
+            // Once cancelled, the remaining chunks are skipped but still answered,
+            // so the pool can tell when the call is over.
+            let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                f.call_cancellable(splitted.part, &cancel)
+            }));
+            let (res, panicked) = match res {
+                Ok(res) => (res.map_err(PoolError::from), false),
+                Err(payload) => {
+                    let message = panic_message(payload.as_ref());
+                    (Err(PoolError::Panicked { part: n, message }), true)
+                }
+            };
+            if self.return_tx.send(SplitPart::new(res, n)).is_err() {
                 return;
             }
-        });
-
-        Self { param_tx }
-    }
-
-    fn send(&self, part: SplitPart<T>) {
-        self.param_tx.send(part).unwrap();
+            if panicked {
+                return self.spawn(f);
+            }
+        }
     }
 }
 
@@ -200,9 +579,15 @@ where
     T: Split,
     U: Join,
 {
-    workers: Vec<Worker<T>>,
-    tx: mpsc::Sender<SplitPart<U>>,
-    rx: mpsc::Receiver<SplitPart<U>>,
+    workers: usize,
+    chunks_per_worker: usize,
+    /// Closed on shutdown, which stops the workers once the queue is empty.
+    queue_tx: Option<mpsc::Sender<Job<T>>>,
+    queue_rx: Queue<T>,
+    handles: Handles,
+    tx: mpsc::Sender<Output<U>>,
+    rx: mpsc::Receiver<Output<U>>,
+    progress: Progress,
 }
 
 impl<T, U> WorkerPool<T, U>
@@ -212,10 +597,16 @@ where
 {
     fn new() -> Self {
         let (tx, rx) = mpsc::channel();
+        let (queue_tx, queue_rx) = mpsc::channel();
         Self {
-            workers: vec![],
+            workers: 0,
+            chunks_per_worker: DEFAULT_CHUNKS_PER_WORKER,
+            queue_tx: Some(queue_tx),
+            queue_rx: Arc::new(Mutex::new(queue_rx)),
+            handles: Arc::new(Mutex::new(vec![])),
             rx,
             tx,
+            progress: Progress::new(),
         }
     }
 
@@ -225,7 +616,14 @@ where
         G: Fn() -> F,
     {
         for _ in 0..n {
-            self.workers.push(Worker::new(g(), self.tx.clone()));
+            let worker = Worker {
+                id: self.workers,
+                queue: self.queue_rx.clone(),
+                return_tx: self.tx.clone(),
+                handles: self.handles.clone(),
+            };
+            worker.spawn(g());
+            self.workers += 1;
         }
     }
 
@@ -245,31 +643,144 @@ where
     {
         Self::with(n, || f.clone())
     }
+
+    /// Set how many chunks per worker the input of a call is split into.
+    ///
+    /// With one chunk per worker, each worker gets exactly one part of the
+    /// input (static scheduling).
+    pub fn with_chunks_per_worker(mut self, chunks: usize) -> Self {
+        assert!(chunks > 0, "chunks per worker must be positive");
+        self.chunks_per_worker = chunks;
+        self
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    pub fn chunks_per_worker(&self) -> usize {
+        self.chunks_per_worker
+    }
 }
 
-impl<T, U> Call<T, U> for WorkerPool<T, U>
+impl<T, U> WorkerPool<T, U>
 where
     T: Split,
     U: Join,
 {
-    fn call(&self, t: T) -> U {
-        let sn = self.workers.len();
-        assert!(sn > 0, "no workers");
+    /// Progress of the current or last call, advanced as each chunk is done.
+    pub fn progress(&self) -> Progress {
+        self.progress.clone()
+    }
 
-        for (worker, part) in zip(&self.workers, t.to_parts(sn)) {
-            worker.send(part);
+    /// Stop the workers and wait for their threads to finish.
+    ///
+    /// Calls made after shutting down fail with `PoolError::NoWorkers`. This is
+    /// also done when the pool is dropped.
+    pub fn shutdown(&mut self) {
+        self.queue_tx = None;
+        self.workers = 0;
+        // A worker that panics on its last chunk pushes its replacement before
+        // it finishes, so keep going until no thread is left.
+        loop {
+            let handle = self.handles.lock().unwrap().pop();
+            match handle {
+                Some(handle) => {
+                    let _ = handle.join();
+                }
+                None => return,
+            }
         }
+    }
+}
+
+impl<T, U> Drop for WorkerPool<T, U>
+where
+    T: Split,
+    U: Join,
+{
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl<T, U> WorkerPool<T, U>
+where
+    T: Split,
+    U: Join,
+{
+    /// Like `call`, but return an error instead of panicking if a worker
+    /// panicked.
+    pub fn try_call(&self, t: T) -> Result<U, PoolError> {
+        self.try_call_cancellable(t, &CancelToken::new())
+    }
+
+    /// Like `call_cancellable`, but return an error instead of panicking if a
+    /// worker panicked.
+    ///
+    /// The token is checked by the workers before each chunk, and passed down to
+    /// the function they call.
+    pub fn try_call_cancellable(&self, t: T, cancel: &CancelToken) -> Result<U, PoolError> {
+        let queue_tx = match self.queue_tx {
+            Some(ref queue_tx) if self.workers > 0 => queue_tx,
+            _ => return Err(PoolError::NoWorkers),
+        };
+        let sn = self.workers * self.chunks_per_worker;
+
+        self.progress.start(sn);
+        for part in t.to_parts(sn) {
+            queue_tx.send((part, cancel.clone())).unwrap();
+        }
+        // Every chunk is answered, even after an error, so that the results of
+        // this call can't be mistaken for those of the next one.
         let mut parts: Vec<SplitPart<U>> = vec![];
+        let mut error: Option<PoolError> = None;
         for _ in 0..sn {
-            parts.push(self.rx.recv().unwrap());
+            let SplitPart { n, part } = self.rx.recv().unwrap();
+            self.progress.advance(1);
+            match part {
+                Ok(part) => parts.push(SplitPart::new(part, n)),
+                // A panic is worth reporting over the cancellations it may cause.
+                Err(e) => match error {
+                    Some(PoolError::Panicked { .. }) => (),
+                    _ => error = Some(e),
+                },
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(SplitPart::join(parts)?),
+        }
+    }
+}
+
+impl<T, U> Call<T, U> for WorkerPool<T, U>
+where
+    T: Split,
+    U: Join,
+{
+    /// Panics if a worker panicked; see `try_call` for a fallible version.
+    fn call(&self, t: T) -> U {
+        self.try_call(t).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn call_cancellable(&self, t: T, cancel: &CancelToken) -> Result<U, Cancelled> {
+        match self.try_call_cancellable(t, cancel) {
+            Ok(u) => Ok(u),
+            Err(PoolError::Cancelled) => Err(Cancelled),
+            Err(e) => panic!("{}", e),
         }
-        SplitPart::join(parts).unwrap()
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{vectorize, Call, Split, SplitPart, Threaded};
+    use super::{
+        vectorize, Call, CancelToken, Cancelled, Join, JoinError, PoolError, ProgressReport, Split,
+        SplitPart, Threaded,
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
 
     fn test_vec_split(length: usize, n: usize) {
         let v: Vec<usize> = (0..length).collect();
@@ -287,6 +798,26 @@ mod test {
         test_vec_split(8, 5);
         test_vec_split(100, 1);
         test_vec_split(55, 47);
+
+        // The first part grows into the whole, in place if it has the room.
+        let mut first = Vec::with_capacity(10);
+        first.extend(0..4);
+        let ptr = first.as_ptr();
+        let joined = Vec::join_vec(vec![first, (4..7).collect(), vec![], (7..10).collect()]);
+        assert_eq!(joined, (0..10).collect::<Vec<i32>>());
+        assert_eq!(joined.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn test_join_errors() {
+        let join = |ns: &[usize]| {
+            let parts = ns.iter().map(|&n| SplitPart::new(vec![n], n)).collect();
+            SplitPart::<Vec<usize>>::join(parts)
+        };
+        assert_eq!(join(&[]), Err(JoinError::NoParts));
+        assert_eq!(join(&[0, 2]), Err(JoinError::OutOfRange { n: 2, parts: 2 }));
+        assert_eq!(join(&[1, 1]), Err(JoinError::Duplicate(1)));
+        assert_eq!(join(&[1, 0]), Ok(vec![0, 1]));
     }
 
     fn mul2(x: i64) -> i64 {
@@ -304,4 +835,103 @@ mod test {
         assert_eq!(res, f.threadpool(10).call(q()));
         assert_eq!(res, f.threadpool(20).call(q()));
     }
+
+    #[test]
+    fn test_worker_pool_chunks() {
+        let q = || (0..100).collect::<Vec<i64>>();
+        let f = vectorize(mul2);
+        let res = f.call(q());
+
+        for chunks in [1, 3, 8, 50] {
+            let pool = f.threadpool(4).with_chunks_per_worker(chunks);
+            assert_eq!(res, pool.call(q()));
+            assert_eq!(res, pool.call(q()));
+        }
+    }
+
+    #[test]
+    fn test_worker_pool_progress() {
+        let pool = vectorize(mul2).threadpool(3).with_chunks_per_worker(5);
+        let progress = pool.progress();
+        assert_eq!(progress.report().fraction(), 1.0);
+        pool.call((0..100).collect::<Vec<i64>>());
+        let report = progress.report();
+        assert_eq!((report.done, report.total), (15, 15));
+        assert!(report.is_finished());
+    }
+
+    #[test]
+    fn test_progress_eta() {
+        let report = ProgressReport {
+            done: 1,
+            total: 4,
+            elapsed: Duration::from_secs(2),
+        };
+        assert_eq!(report.fraction(), 0.25);
+        assert_eq!(report.eta(), Some(Duration::from_secs(6)));
+        assert_eq!(report.to_string(), " 25% (ETA 6.0s)");
+    }
+
+    #[test]
+    fn test_worker_pool_cancel() {
+        let q = || (0..100).collect::<Vec<i64>>();
+        let cancel = CancelToken::new();
+        let token = cancel.clone();
+        let f = vectorize(mul2);
+        // Cancels the call from inside its first chunk.
+        let pool = (move |v: Vec<i64>| {
+            token.cancel();
+            f.call(v)
+        })
+        .threadpool(1)
+        .with_chunks_per_worker(8);
+
+        assert_eq!(pool.call_cancellable(q(), &cancel), Err(Cancelled));
+        assert_eq!(pool.call_cancellable(q(), &cancel), Err(Cancelled));
+        assert_eq!(
+            pool.call_cancellable(q(), &CancelToken::new()),
+            Ok(vectorize(mul2).call(q()))
+        );
+    }
+
+    #[test]
+    fn test_worker_pool_panic() {
+        let q = |n: i64| (0..n).collect::<Vec<i64>>();
+        let f = vectorize(mul2);
+        let pool = (move |v: Vec<i64>| {
+            assert!(!v.contains(&13), "unlucky");
+            f.call(v)
+        })
+        .threadpool(2)
+        .with_chunks_per_worker(4);
+
+        for _ in 0..3 {
+            match pool.try_call(q(20)) {
+                Err(PoolError::Panicked { message, .. }) => assert_eq!(message, "unlucky"),
+                res => panic!("expected a panic, got {:?}", res),
+            }
+            // The workers that panicked were replaced.
+            assert_eq!(pool.try_call(q(10)), Ok(vectorize(mul2).call(q(10))));
+        }
+    }
+
+    #[test]
+    fn test_worker_pool_shutdown() {
+        let alive = Arc::new(());
+        let token = alive.clone();
+        let f = vectorize(mul2);
+        let mut pool = (move |v: Vec<i64>| {
+            let _ = &token;
+            assert!(!v.contains(&13), "unlucky");
+            f.call(v)
+        })
+        .threadpool(3);
+        assert!(pool.try_call((0..20).collect()).is_err());
+        assert_eq!(Arc::strong_count(&alive), 4);
+
+        pool.shutdown();
+        // The functions are dropped along with the threads that ran them.
+        assert_eq!(Arc::strong_count(&alive), 1);
+        assert_eq!(pool.try_call(vec![1]), Err(PoolError::NoWorkers));
+    }
 }
@@ -0,0 +1,317 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::coord::{Point, Viewbox};
+use crate::solver::{D2ArrayLike, Lazy, MbState, Solver};
+use crate::threads::{CancelToken, Cancelled, Join, JoinTiles, SplitTiles, Tile};
+
+/// Side length of a tile, in pixels.
+pub const TILE_SIZE: i64 = 64;
+
+/// Address of a tile, web-map style: a zoom level and the tile's column and row.
+///
+/// Tiles are laid out on the global pixel grid of the viewbox, so the
+/// tile (x, y) covers pixels `x * TILE_SIZE..(x + 1) * TILE_SIZE` horizontally and
+/// `y * TILE_SIZE..(y + 1) * TILE_SIZE` vertically, at the zoom level given by
+/// `Viewbox::zoom_level`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TileKey {
+    pub level: u64,
+    pub x: i64,
+    pub y: i64,
+}
+
+impl TileKey {
+    pub fn new(level: u64, x: i64, y: i64) -> Self {
+        Self { level, x, y }
+    }
+
+    pub fn origin(&self) -> Point<i64> {
+        Point::new(self.x * TILE_SIZE, self.y * TILE_SIZE)
+    }
+}
+
+impl Viewbox {
+    /// Keys of all the tiles that overlap this viewbox, in row-major order.
+    pub fn tile_keys(&self) -> Vec<TileKey> {
+        let level = self.zoom_level();
+        let origin = self.origin();
+        let x0 = origin.x.div_euclid(TILE_SIZE);
+        let x1 = (origin.x + self.width - 1).div_euclid(TILE_SIZE);
+        let y0 = origin.y.div_euclid(TILE_SIZE);
+        let y1 = (origin.y + self.height - 1).div_euclid(TILE_SIZE);
+        let mut keys = vec![];
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                keys.push(TileKey::new(level, x, y));
+            }
+        }
+        keys
+    }
+
+    /// Viewbox covering exactly the given tile, on the same pixel grid as this one.
+    pub fn tile(&self, key: &TileKey) -> Viewbox {
+        let half = TILE_SIZE / 2;
+        Viewbox {
+            offset: key.origin().add(&Point::new(half, half)),
+            width: TILE_SIZE,
+            height: TILE_SIZE,
+            ..*self
+        }
+    }
+}
+
+/// In-memory LRU cache of solved tiles.
+pub struct TileCache<T> {
+    capacity: usize,
+    tick: u64,
+    tiles: HashMap<TileKey, (u64, T)>,
+}
+
+impl<T> TileCache<T> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "tile cache capacity must be positive");
+        Self {
+            capacity,
+            tick: 0,
+            tiles: HashMap::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    pub fn contains(&self, key: &TileKey) -> bool {
+        self.tiles.contains_key(key)
+    }
+
+    pub fn get(&mut self, key: &TileKey) -> Option<&T> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.tiles.get_mut(key).map(|(used, tile)| {
+            *used = tick;
+            &*tile
+        })
+    }
+
+    pub fn insert(&mut self, key: TileKey, tile: T) {
+        self.insert_pinned(key, tile, &HashSet::new());
+    }
+
+    /// Insert a tile without evicting any of `pinned`, e.g. the other tiles of
+    /// a frame; the cache grows past its capacity if they don't fit.
+    fn insert_pinned(&mut self, key: TileKey, tile: T, pinned: &HashSet<TileKey>) {
+        if !self.tiles.contains_key(&key) {
+            while self.tiles.len() >= self.capacity && self.evict(pinned) {}
+        }
+        self.tick += 1;
+        self.tiles.insert(key, (self.tick, tile));
+    }
+
+    /// Mark a tile as used, if it is cached.
+    fn touch(&mut self, key: &TileKey) {
+        self.get(key);
+    }
+
+    /// Look a tile up without marking it as used.
+    fn peek(&self, key: &TileKey) -> Option<&T> {
+        self.tiles.get(key).map(|(_, tile)| tile)
+    }
+
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+    }
+
+    /// Evict the least recently used tile that is not pinned; false if there
+    /// is none.
+    fn evict(&mut self, pinned: &HashSet<TileKey>) -> bool {
+        let oldest = self
+            .tiles
+            .iter()
+            .filter(|(key, _)| !pinned.contains(key))
+            .min_by_key(|(_, (used, _))| *used)
+            .map(|(key, _)| *key);
+        match oldest {
+            Some(key) => self.tiles.remove(&key).is_some(),
+            None => false,
+        }
+    }
+}
+
+impl<T> TileCache<T>
+where
+    T: D2ArrayLike + MbState + Join + Clone,
+{
+    /// Solve the `missing` tiles of `position`.
+    ///
+    /// All missing tiles are stacked into a single pending state so they are
+    /// solved with one solver call, and therefore spread over all the workers of
+    /// a pool.
+    fn solve_missing(
+        position: &Viewbox,
+        missing: &[TileKey],
+        solver: &dyn Solver<T>,
+        cancel: &CancelToken,
+    ) -> Result<Vec<(TileKey, T)>, Cancelled> {
+        if missing.is_empty() {
+            return Ok(vec![]);
+        }
+        let tiles = missing.iter().map(|key| position.tile(key)).collect();
+        let solved = solver.solve_lazy_cancellable(Lazy::Pending(tiles), cancel)?;
+        let size = TILE_SIZE as usize;
+        let tiles = solved.split_tiles(size, size).into_iter().map(|t| t.part);
+        Ok(missing.iter().copied().zip(tiles).collect())
+    }
+}
+
+/// Renders frames through a tile cache.
+///
+/// This lets `Mandelbrot` hold a `TileCache` without requiring `D2ArrayLike` from
+/// every state type.
+pub trait TileRender<T> {
+    /// Assemble the frame for `position` from cached tiles, solving only the
+    /// tiles that are missing from the cache.
+    ///
+    /// Nothing is added to the cache when cancelled.
+    fn render(
+        &mut self,
+        position: &Viewbox,
+        solver: &dyn Solver<T>,
+        cancel: &CancelToken,
+    ) -> Result<T, Cancelled>;
+}
+
+impl<T> TileRender<T> for TileCache<T>
+where
+    T: D2ArrayLike + MbState + Join + Clone,
+{
+    fn render(
+        &mut self,
+        position: &Viewbox,
+        solver: &dyn Solver<T>,
+        cancel: &CancelToken,
+    ) -> Result<T, Cancelled> {
+        let keys = position.tile_keys();
+        let mut hits = vec![];
+        let mut missing = vec![];
+        for key in &keys {
+            match self.peek(key) {
+                Some(tile) => hits.push((*key, tile)),
+                None => missing.push(*key),
+            }
+        }
+        let solved = Self::solve_missing(position, &missing, solver, cancel)?;
+        let origin = position.origin().mul(-1);
+        let tiles = hits
+            .into_iter()
+            .chain(solved.iter().map(|(key, tile)| (*key, tile)))
+            .map(|(key, tile)| Tile::new(key.origin().add(&origin), tile));
+        let (width, height) = (position.width as usize, position.height as usize);
+        let frame = T::join_tiles(width, height, tiles);
+
+        // The tiles already cached are used by this frame too, so they must
+        // outlive the ones it adds.
+        for key in &keys {
+            self.touch(key);
+        }
+        let pinned: HashSet<TileKey> = keys.into_iter().collect();
+        for (key, tile) in solved {
+            self.insert_pinned(key, tile, &pinned);
+        }
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::solver::{VecSolver, VecState};
+
+    #[test]
+    fn test_tile_cache_lru() {
+        let mut cache: TileCache<usize> = TileCache::new(2);
+        cache.insert(TileKey::new(0, 0, 0), 0);
+        cache.insert(TileKey::new(0, 1, 0), 1);
+        assert_eq!(cache.get(&TileKey::new(0, 0, 0)), Some(&0));
+        cache.insert(TileKey::new(0, 2, 0), 2);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains(&TileKey::new(0, 0, 0)));
+        assert!(!cache.contains(&TileKey::new(0, 1, 0)));
+        assert!(cache.contains(&TileKey::new(0, 2, 0)));
+    }
+
+    fn check_render(cache: &mut TileCache<VecState>, position: Viewbox) {
+        let solver = VecSolver::default();
+        let full: VecState = solver.solve(position.into());
+        let tiled: VecState = cache
+            .render(&position, &solver, &CancelToken::new())
+            .unwrap();
+        for (a, b) in full.state.iter().zip(tiled.state.iter()) {
+            assert_eq!(a.i, b.i);
+        }
+    }
+
+    #[test]
+    fn test_frame_larger_than_capacity() {
+        let mut cache = TileCache::new(4);
+        let position = Viewbox::initial(200, 150);
+        let tiles = position.tile_keys().len();
+        assert!(tiles > 4);
+        check_render(&mut cache, position);
+        assert_eq!(cache.len(), tiles);
+        // The next frame evicts the extra tiles again.
+        let mut moved = position;
+        moved.pan(4096, 0);
+        check_render(&mut cache, moved);
+        assert_eq!(cache.len(), moved.tile_keys().len());
+    }
+
+    #[test]
+    fn test_revisit_after_eviction() {
+        let mut cache = TileCache::new(6);
+        let a = Viewbox::initial(128, 128);
+        let mut b = a;
+        b.pan(1024, 0);
+        check_render(&mut cache, a);
+        check_render(&mut cache, b);
+        check_render(&mut cache, a);
+        check_render(&mut cache, a);
+    }
+
+    #[test]
+    fn test_tile_keys_cover_viewbox() {
+        let mut viewbox = Viewbox::initial(100, 70);
+        viewbox.pan(-5, 3);
+        let keys = viewbox.tile_keys();
+        assert_eq!(keys.len(), 4);
+        assert_eq!(keys[0].origin().x, -64);
+        assert_eq!(keys[0].origin().y, -64);
+    }
+
+    #[test]
+    fn test_tiled_render_matches_full_solve() {
+        let solver = VecSolver::default();
+        let mut cache = TileCache::new(64);
+        let mut position = Viewbox::initial(90, 70);
+        for _ in 0..2 {
+            let full: VecState = solver.solve(position.into());
+            let tiled: VecState = cache
+                .render(&position, &solver, &CancelToken::new())
+                .unwrap();
+            for y in 0..70 {
+                for x in 0..90 {
+                    assert_eq!(full.i_value(x, y), tiled.i_value(x, y));
+                }
+            }
+            position.pan(17, -23);
+        }
+    }
+}